@@ -4,6 +4,28 @@
 
 use std::collections::HashSet;
 
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The 6 by 10 letters messages are written in, as far as they're known.
+#[rustfmt::skip]
+const FONT: [(char, [&str; 10]); 15] = [
+    ('A', ["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', ["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', [".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', ["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', [".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', ["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', ["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+    ('K', ["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', ["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', ["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', ["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', ["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', ["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', ["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
 #[derive(Default)]
 struct Grid {
     xmin: Option<i32>,
@@ -53,66 +75,201 @@ fn parse_line(line: &str) -> Point {
     }
 }
 
-fn part1(points: &mut Vec<Point>) -> Option<(String, usize)> {
-    let mut most_neighbors: Option<(usize, usize)> = None;
-    for step in 0.. {
-        let mut grid = Grid {
-            ..Default::default()
-        };
+impl Point {
+    fn at(&self, t: i64) -> (i64, i64) {
+        (
+            i64::from(self.pos.0) + i64::from(self.vel.0) * t,
+            i64::from(self.pos.1) + i64::from(self.vel.1) * t,
+        )
+    }
+}
+
+/// The `(width, height)` of the smallest box holding every point at second `t`.
+fn extent(points: &[Point], t: i64) -> (i64, i64) {
+    let positions = points.iter().map(|p| p.at(t));
+    let (xs, ys): (Vec<_>, Vec<_>) = positions.unzip();
+    let span = |v: &[i64]| v.iter().max().unwrap() - v.iter().min().unwrap();
+    (span(&xs), span(&ys))
+}
 
-        for point in points.iter_mut() {
-            // Set grid with points *prior* to update so that if the count of neighbors is
-            // decreasing we can refer back to the grid prior to the change
-            grid.pointset.insert((point.pos.0, point.pos.1));
-
-            point.pos.0 += point.vel.0;
-            grid.xmin = grid
-                .xmin
-                .min(Some(point.pos.0)).or(Some(point.pos.0));
-            grid.xmax = grid.xmax.max(Some(point.pos.0));
-
-            point.pos.1 += point.vel.1;
-            grid.ymin = grid
-                .ymin
-                .min(Some(point.pos.1)).or(Some(point.pos.1));
-            grid.ymax = grid.ymax.max(Some(point.pos.1));
+/// The first second `t` in `0..=hi` at which `f` stops decreasing. `f` must be convex over the
+/// range, so that its step-to-step differences never go down, which makes this a binary search
+/// for the first difference that isn't negative.
+fn convex_argmin(f: impl Fn(i64) -> i64, hi: i64) -> i64 {
+    let (mut lo, mut hi) = (0, hi);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if f(mid + 1) < f(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
         }
+    }
+    lo
+}
 
-        let neighbor_count = points.iter().fold(0, |acc, point| {
-            acc + points
-                .iter()
-                .filter(|p| {
-                    (p.pos.0 - point.pos.0).abs() <= 1 && (p.pos.1 - point.pos.1).abs() <= 1
-                })
-                .count()
-        });
-        if let Some((_step, old_count)) = most_neighbors {
-            // If neighbor_count is increasing, keep going
-            if neighbor_count >= old_count {
-                most_neighbors = Some((step, neighbor_count));
-
-            // Each star is double counted, and will on average have 2 neighbor stars, so only
-            // print if the neighbor_count is somewhat close to 4 times the number of stars
-            } else if neighbor_count > (points.len() * 2) {
-                return Some((grid.to_string(), step));
+/// Finds the second at which the points fit in the smallest bounding box, returning it along
+/// with where every point is at that moment.
+///
+/// Width and height over time are each the gap between the largest and smallest of a set of
+/// straight lines, so each is convex and their minima can be found by binary search. Before both
+/// minima the area can only shrink, and after both it can only grow, so the smallest area lies
+/// between the two and only those seconds need checking.
+fn converge(points: &[Point]) -> Option<(usize, Vec<(i64, i64)>)> {
+    if points.is_empty() {
+        return None;
+    }
+
+    // Integer velocities close any gap by at least one per second, so neither dimension can keep
+    // shrinking for longer than it is wide to begin with
+    let (width, height) = extent(points, 0);
+    let hi = width.max(height);
+    let tw = convex_argmin(|t| extent(points, t).0, hi);
+    let th = convex_argmin(|t| extent(points, t).1, hi);
+
+    let t = (tw.min(th)..=tw.max(th))
+        .min_by_key(|&t| {
+            let (w, h) = extent(points, t);
+            (w + 1) * (h + 1)
+        })
+        .unwrap();
+    Some((t as usize, points.iter().map(|p| p.at(t)).collect()))
+}
+
+impl Grid {
+    fn new(positions: &[(i64, i64)]) -> Self {
+        let pointset = positions
+            .iter()
+            .map(|&(x, y)| (x as i32, y as i32))
+            .collect::<HashSet<_>>();
+        Grid {
+            xmin: pointset.iter().map(|p| p.0).min(),
+            xmax: pointset.iter().map(|p| p.0).max(),
+            ymin: pointset.iter().map(|p| p.1).min(),
+            ymax: pointset.iter().map(|p| p.1).max(),
+            pointset,
+        }
+    }
+}
+
+/// Reads the letters spelled out by the points.
+///
+/// Each run of columns with at least one point in it is taken as a glyph and matched against
+/// `FONT`; anything that doesn't match fails with a picture of the glyph.
+fn ocr(positions: &[(i64, i64)]) -> Result<String> {
+    let grid = Grid::new(positions);
+    let (xmin, xmax) = (grid.xmin.ok_or("no points")?, grid.xmax.ok_or("no points")?);
+    let (ymin, ymax) = (grid.ymin.ok_or("no points")?, grid.ymax.ok_or("no points")?);
+    let column_empty = |x| (ymin..=ymax).all(|y| !grid.pointset.contains(&(x, y)));
+
+    let mut text = String::new();
+    let mut x = xmin;
+    while x <= xmax {
+        if column_empty(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x <= xmax && !column_empty(x) {
+            x += 1;
+        }
+        let glyph = (ymin..=ymax)
+            .map(|y| {
+                (start..x)
+                    .map(|x| {
+                        if grid.pointset.contains(&(x, y)) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        match FONT.iter().find(|(_c, rows)| rows[..] == glyph[..]) {
+            Some((c, _rows)) => text.push(*c),
+            None => {
+                return Err(
+                    format!("unrecognised glyph after {:?}:\n{}", text, glyph.join("\n")).into(),
+                )
             }
-        } else {
-            most_neighbors = Some((step, neighbor_count));
         }
     }
-    None
+    Ok(text)
 }
 
-fn main() -> std::io::Result<()> {
+fn part1(points: &[Point]) -> Result<(String, usize)> {
+    let (step, positions) = converge(points).ok_or("no points")?;
+    Ok((ocr(&positions)?, step))
+}
+
+/// Frames bigger than this either way are refused, as the points are too spread out to show
+/// anything but noise.
+const MAX_FRAME_SIZE: i64 = 4096;
+
+/// A binary (`P4`) PBM image of the points, cropped to their bounding box, with a black pixel
+/// for every point.
+fn to_pbm(positions: &[(i64, i64)]) -> Result<Vec<u8>> {
+    let (xs, ys): (Vec<_>, Vec<_>) = positions.iter().copied().unzip();
+    let (xmin, xmax) = (
+        xs.iter().min().ok_or("no points")?,
+        xs.iter().max().unwrap(),
+    );
+    let (ymin, ymax) = (ys.iter().min().unwrap(), ys.iter().max().unwrap());
+    let (width, height) = (xmax - xmin + 1, ymax - ymin + 1);
+    if width > MAX_FRAME_SIZE || height > MAX_FRAME_SIZE {
+        return Err(format!("a {}x{} frame is too big to draw", width, height).into());
+    }
+
+    // Each row is packed into bytes, most significant bit first, and padded to a whole byte
+    let stride = (width as usize).div_ceil(8);
+    let mut bits = vec![0u8; stride * height as usize];
+    for &(x, y) in positions {
+        let (col, row) = ((x - xmin) as usize, (y - ymin) as usize);
+        bits[row * stride + col / 8] |= 0x80 >> (col % 8);
+    }
+
+    let mut pbm = format!("P4\n{} {}\n", width, height).into_bytes();
+    pbm.extend_from_slice(&bits);
+    Ok(pbm)
+}
 
+/// Pictures of the points every second from `window` seconds before they converge to `window`
+/// seconds after, each labelled with its second. Every frame is cropped to the points in it, so
+/// the picture zooms in as they come together.
+fn time_lapse(points: &[Point], window: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+    let (step, _positions) = converge(points).ok_or("no points")?;
+    (step.saturating_sub(window)..=step + window)
+        .map(|t| {
+            let positions = points.iter().map(|p| p.at(t as i64)).collect::<Vec<_>>();
+            Ok((t, to_pbm(&positions)?))
+        })
+        .collect()
+}
+
+fn main() -> Result<()> {
     let input = std::fs::read_to_string("day10/input.txt")?;
-    let mut points: Vec<_> = input.lines().map(|line| parse_line(line)).collect();
+    let points: Vec<_> = input.lines().map(parse_line).collect();
 
-    if let Some((stars, steps)) = part1(&mut points) {
-        println!("part 1:\n{}", stars);
-        println!("part 2: {}", steps);
-    } else {
-        println!("No solution found");
+    let (message, steps) = part1(&points)?;
+    println!("part 1: {}", message);
+    println!("part 2: {}", steps);
+
+    // Optionally save the seconds around the message as numbered frames, e.g.
+    // `day10 --frames frames/ 5`
+    let args = std::env::args().collect::<Vec<_>>();
+    if let [_, flag, dir, rest @ ..] = &args[..] {
+        if flag == "--frames" {
+            let window = match rest.first() {
+                Some(window) => window.parse()?,
+                None => 10,
+            };
+            std::fs::create_dir_all(dir)?;
+            for (t, pbm) in time_lapse(&points, window)? {
+                let path = std::path::Path::new(dir).join(format!("frame-{:05}.pbm", t));
+                std::fs::write(path, pbm)?;
+            }
+        }
     }
     Ok(())
 }
@@ -133,10 +290,9 @@ mod tests {
         )
     }
 
-    #[test]
     #[test]
     fn test_part1() {
-        let mut points = "
+        let points = "
             position=< 9,  1> velocity=< 0,  2>
             position=< 7,  0> velocity=<-1,  0>
             position=< 3, -2> velocity=<-1,  1>
@@ -171,8 +327,98 @@ mod tests {
             "
         .lines()
         .filter(|line| !line.trim().is_empty())
-        .map(|line| parse_line(line))
+        .map(parse_line)
         .collect::<Vec<_>>();
-        println!("{}", part1(&mut points).expect("No solution found").0);
+        let (step, positions) = converge(&points).expect("No solution found");
+        let stars = Grid::new(&positions).to_string();
+        assert_eq!(step, 3);
+        assert_eq!(
+            stars,
+            "\
+#...#..###
+#...#...#.
+#...#...#.
+#####...#.
+#...#...#.
+#...#...#.
+#...#...#.
+#...#..###"
+        );
+
+        assert_eq!(positions.len(), points.len());
+        assert!(positions.contains(&(0, 0)));
+        assert!(converge(&[]).is_none());
+    }
+
+    #[test]
+    fn test_convex_argmin() {
+        assert_eq!(convex_argmin(|t| (t - 7).abs(), 100), 7);
+        assert_eq!(convex_argmin(|t| (t - 7).abs(), 3), 3);
+        assert_eq!(convex_argmin(|t| (t - 7).abs().max(2), 100), 5);
+        assert_eq!(convex_argmin(|t| t, 100), 0);
+    }
+
+    #[test]
+    fn test_ocr() {
+        let positions = |rows: &[&str]| {
+            let mut positions = Vec::new();
+            for (y, row) in rows.iter().enumerate() {
+                for (x, c) in row.chars().enumerate() {
+                    if c == '#' {
+                        positions.push((x as i64 - 20, y as i64 + 5));
+                    }
+                }
+            }
+            positions
+        };
+
+        let mut rows = vec![String::new(); 10];
+        for c in "ZEBRA".chars() {
+            let (_c, glyph) = FONT.iter().find(|(f, _glyph)| *f == c).unwrap();
+            for (row, line) in rows.iter_mut().zip(glyph.iter()) {
+                row.push_str(line);
+                row.push_str("..");
+            }
+        }
+        let rows = rows.iter().map(|r| r.as_str()).collect::<Vec<_>>();
+        assert_eq!(ocr(&positions(&rows)).unwrap(), "ZEBRA");
+
+        let err = ocr(&positions(&["#.#", ".#.", "#.#"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unrecognised glyph after \"\":\n#.#\n.#.\n#.#"
+        );
+    }
+
+    #[test]
+    fn test_to_pbm() {
+        let pbm = to_pbm(&[(3, 7), (12, 7), (4, 8)]).unwrap();
+        let mut expected = b"P4\n10 2\n".to_vec();
+        expected.extend_from_slice(&[0b1000_0000, 0b0100_0000, 0b0100_0000, 0b0000_0000]);
+        assert_eq!(pbm, expected);
+
+        assert!(to_pbm(&[(0, 0), (MAX_FRAME_SIZE, 0)]).is_err());
+    }
+
+    #[test]
+    fn test_time_lapse() {
+        let points = vec![
+            Point {
+                pos: (0, 0),
+                vel: (1, 0),
+            },
+            Point {
+                pos: (10, 0),
+                vel: (-1, 0),
+            },
+        ];
+        let frames = time_lapse(&points, 2).unwrap();
+        let seconds = frames.iter().map(|(t, _pbm)| *t).collect::<Vec<_>>();
+        assert_eq!(seconds, vec![3, 4, 5, 6, 7]);
+        let sizes = frames
+            .iter()
+            .map(|(_t, pbm)| pbm.split(|&b| b == b'\n').nth(1).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![&b"5 1"[..], b"3 1", b"1 1", b"3 1", b"5 1"]);
     }
 }
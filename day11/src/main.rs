@@ -1,75 +1,144 @@
-use std::collections::HashMap;
-
+/// The power level of the cell at `coords`: the hundreds digit of `((x + 10) * y + serial) *
+/// (x + 10)`, less 5.
+///
+/// Only the last three digits of the product matter, so it's worked out modulo 1000 throughout,
+/// which can't overflow for any coordinates or serial number.
 fn power_level(coords: (u32, u32), grid_serial_number: u32) -> i32 {
-    let rack_id = coords.0 + 10;
-    let mut power_level = coords.1 * rack_id + grid_serial_number;
-    power_level *= rack_id;
-    power_level = power_level
-        .to_string()
-        .chars()
-        .rev()
-        .nth(2)
-        .expect("No hundreds digit")
-        .to_digit(10)
-        .expect("Unable to parse as digit");
-    power_level as i32 - 5
+    let rack_id = (u64::from(coords.0) + 10) % 1000;
+    let y = u64::from(coords.1) % 1000;
+    let serial = u64::from(grid_serial_number) % 1000;
+    let last_digits = (rack_id * y + serial) * rack_id % 1000;
+    (last_digits / 100) as i32 - 5
 }
 
-fn sized_power_level(
-    start_coords: (u32, u32),
+/// The power levels of a square grid of fuel cells, numbered from 1 in both directions, kept as a
+/// summed-area table so the total of any rectangle takes four lookups.
+struct PowerGrid {
     size: u32,
-    lookup_table: &HashMap<(u32, u32), i32>,
-) -> i32 {
-    (start_coords.0..=300.min(start_coords.0 + size))
-        .flat_map(|x| {
-            (start_coords.1..=300.min(start_coords.1 + size))
-                .map(move |y| lookup_table.get(&(x, y)).unwrap())
-        })
-        .sum()
-}
-
-fn part1(lookup_table: &HashMap<(u32, u32), i32>) -> (u32, u32) {
-    (1..=300u32)
-        .flat_map(|x| (1..=300u32).map(move |y| (x, y)))
-        .max_by_key(|(x, y)| sized_power_level((*x, *y), 3, lookup_table))
-        .expect("No max found")
+    /// `sums[y * (size + 1) + x]` is the total power of every cell up and left of `(x, y)`
+    /// inclusive, with an extra row and column of zeros for `x == 0` and `y == 0`. Kept as `i64`
+    /// since a big enough grid's totals don't fit in an `i32`.
+    sums: Vec<i64>,
 }
 
-fn part2(lookup_table: &HashMap<(u32, u32), i32>) -> (u32, u32, u32, i32) {
-    let mut best: Option<(u32, u32, u32, i32)> = None;
-    for x in 1..=300u32 {
-        for y in 1..=300u32 {
-            let mut prev = 0;
-            for s in 1..=(300 - x).min(300 - y) {
-                prev += (0..s)
-                    .map(|dy| lookup_table.get(&(x + s - 1, y + dy)).unwrap())
-                    .sum::<i32>();
-                prev += (0..(s - 1))
-                    .map(|dx| lookup_table.get(&(x + dx, y + s - 1)).unwrap())
-                    .sum::<i32>();
-                if let Some(val) = best {
-                    if val.3 <= prev {
-                        best = Some((x, y, s, prev));
-                    }
-                } else {
-                    best = Some((x, y, s, prev));
-                }
+impl PowerGrid {
+    fn new(size: u32, grid_serial_number: u32) -> Self {
+        let stride = size as usize + 1;
+        let mut sums = vec![0; stride * stride];
+        for y in 1..=size {
+            for x in 1..=size {
+                let i = y as usize * stride + x as usize;
+                sums[i] = i64::from(power_level((x, y), grid_serial_number))
+                    + sums[i - 1]
+                    + sums[i - stride]
+                    - sums[i - stride - 1];
             }
         }
+        PowerGrid { size, sums }
+    }
+
+    fn sum_to(&self, x: u32, y: u32) -> i64 {
+        self.sums[y as usize * (self.size as usize + 1) + x as usize]
+    }
+
+    /// Total power of the `width` by `height` rectangle with its top-left cell at `(x, y)`, which
+    /// must lie entirely inside the grid.
+    fn rect_sum(&self, (x, y): (u32, u32), width: u32, height: u32) -> i64 {
+        let fits = |start: u32, len: u32| start >= 1 && len <= self.size - (start - 1);
+        assert!(
+            x <= self.size && y <= self.size && fits(x, width) && fits(y, height),
+            "{}x{} rectangle at {},{} is outside the grid",
+            width,
+            height,
+            x,
+            y
+        );
+        let (x0, y0, x1, y1) = (x - 1, y - 1, x - 1 + width, y - 1 + height);
+        self.sum_to(x1, y1) - self.sum_to(x0, y1) - self.sum_to(x1, y0) + self.sum_to(x0, y0)
+    }
+
+    fn square_sum(&self, coords: (u32, u32), size: u32) -> i64 {
+        self.rect_sum(coords, size, size)
+    }
+
+    /// The top-left corner and power of the most powerful `size` by `size` square, or `None` if
+    /// no square that size fits in the grid.
+    fn best_square(&self, size: u32) -> Option<(u32, u32, i64)> {
+        if size == 0 || size > self.size {
+            return None;
+        }
+        let last = self.size + 1 - size;
+        (1..=last)
+            .flat_map(|x| (1..=last).map(move |y| (x, y)))
+            .map(|(x, y)| (x, y, self.square_sum((x, y), size)))
+            .max_by_key(|&(_x, _y, power)| power)
+    }
+
+    /// The top-left corner, size and power of the most powerful square of any size, or `None` for
+    /// an empty grid.
+    fn best_any_square(&self) -> Option<(u32, u32, u32, i64)> {
+        (1..=self.size)
+            .filter_map(|size| {
+                let (x, y, power) = self.best_square(size)?;
+                Some((x, y, size, power))
+            })
+            .max_by_key(|&(_x, _y, _size, power)| power)
     }
-    best.unwrap()
+}
+
+/// The most powerful square of any size, as `(x, y, size, power)`, in a `size` by `size` grid for
+/// each of `serials`, in the same order, or `None` for each if `size` is 0. The serials are split into one run per available core,
+/// and each run is worked through a grid at a time in its own thread.
+fn best_squares(serials: &[u32], size: u32) -> Vec<Option<(u32, u32, u32, i64)>> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = serials.len().div_ceil(workers).max(1);
+    std::thread::scope(|scope| {
+        let handles = serials
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|&serial| PowerGrid::new(size, serial).best_any_square())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Grid thread panicked"))
+            .collect()
+    })
+}
+
+fn part1(grid: &PowerGrid) -> Option<(u32, u32)> {
+    let (x, y, _power) = grid.best_square(3)?;
+    Some((x, y))
+}
+
+fn part2(grid: &PowerGrid) -> Option<(u32, u32, u32, i64)> {
+    grid.best_any_square()
 }
 
 fn main() {
+    // Any serial numbers given as arguments are solved instead, e.g. `day11 18 42`
+    let serials = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse().expect("Serial number must be a number"))
+        .collect::<Vec<u32>>();
+    if !serials.is_empty() {
+        for (serial, best) in serials.iter().zip(best_squares(&serials, 300)) {
+            let (x, y, size, power) = best.expect("A 300x300 grid has squares");
+            println!("{}: {},{},{} (power {})", serial, x, y, size, power);
+        }
+        return;
+    }
+
     let input = 7400;
-    let lookup_table: HashMap<(u32, u32), i32> = (1..=300)
-        .flat_map(|x| {
-            (1..=300).map(move |y| ((x, y), power_level((x, y), input)))
-        })
-        .collect();
-    let p1 = part1(&lookup_table);
+    let grid = PowerGrid::new(300, input);
+    let p1 = part1(&grid).expect("A 300x300 grid has 3x3 squares");
     println!("part1: {},{}", p1.0, p1.1);
-    let p2 = part2(&lookup_table);
+    let p2 = part2(&grid).expect("A 300x300 grid has squares");
     println!("part2: {},{},{}", p2.0, p2.1, p2.2);
 }
 
@@ -82,16 +151,77 @@ mod tests {
         assert_eq!(power_level((122, 79), 57), -5);
         assert_eq!(power_level((217, 196), 39), 0);
         assert_eq!(power_level((101, 153), 71), 4);
+        assert_eq!(power_level((3, 5), 8), 4);
+        assert_eq!(power_level((0, 0), 0), -5);
+        assert_eq!(power_level((1, 1), 1), -4);
+
+        // Agrees with the product worked out in full wherever that fits
+        for (x, y, serial) in [(u32::MAX, 7, 18), (90_000, 90_000, 7400), (12, 3, u32::MAX)] {
+            let rack_id = u128::from(x) + 10;
+            let full = (rack_id * u128::from(y) + u128::from(serial)) * rack_id;
+            assert_eq!(power_level((x, y), serial), (full / 100 % 10) as i32 - 5);
+        }
+    }
+
+    #[test]
+    fn test_sums() {
+        let grid = PowerGrid::new(300, 18);
+        assert_eq!(grid.square_sum((33, 45), 3), 29);
+        assert_eq!(grid.square_sum((90, 269), 16), 113);
+
+        let brute = (21..21 + 7)
+            .flat_map(|x| (60..60 + 4).map(move |y| power_level((x, y), 18)))
+            .map(i64::from)
+            .sum::<i64>();
+        assert_eq!(grid.rect_sum((21, 60), 7, 4), brute);
+        assert_eq!(grid.rect_sum((1, 1), 300, 300), grid.sum_to(300, 300));
+
+        let small = PowerGrid::new(5, 18);
+        assert_eq!(small.rect_sum((2, 3), 4, 3), grid.rect_sum((2, 3), 4, 3));
+        assert_eq!(small.best_square(5).unwrap().0, 1);
+
+        // Sizes that don't fit, and grids with nothing in them
+        assert_eq!(small.best_square(0), None);
+        assert_eq!(small.best_square(6), None);
+        assert_eq!(PowerGrid::new(0, 18).best_any_square(), None);
+        assert_eq!(best_squares(&[18], 0), vec![None]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rect_sum_outside() {
+        PowerGrid::new(5, 18).rect_sum((0, 1), 2, 2);
+    }
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(part1(&PowerGrid::new(300, 18)), Some((33, 45)));
+        assert_eq!(part1(&PowerGrid::new(300, 42)), Some((21, 61)));
+        assert_eq!(part1(&PowerGrid::new(2, 42)), None);
     }
 
     #[test]
     fn test_part2() {
-        let input = 42;
-        let lookup_table: HashMap<(u32, u32), i32> = (1..=300)
-            .flat_map(|x| {
-                (1..=300).map(move |y| ((x, y), power_level((x, y), input)))
-            })
-            .collect();
-        assert_eq!(part2(&lookup_table), (232, 251, 12, 119));
+        let grid = PowerGrid::new(300, 42);
+        assert_eq!(part2(&grid), Some((232, 251, 12, 119)));
+        let grid = PowerGrid::new(300, 18);
+        assert_eq!(part2(&grid), Some((90, 269, 16, 113)));
+    }
+
+    #[test]
+    fn test_best_squares() {
+        assert_eq!(
+            best_squares(&[18, 42], 300),
+            vec![Some((90, 269, 16, 113)), Some((232, 251, 12, 119))]
+        );
+        assert_eq!(best_squares(&[], 300), vec![]);
+
+        // More serials than cores still come back in order
+        let serials = (1..=40).collect::<Vec<_>>();
+        let expected = serials
+            .iter()
+            .map(|&serial| PowerGrid::new(20, serial).best_any_square())
+            .collect::<Vec<_>>();
+        assert_eq!(best_squares(&serials, 20), expected);
     }
 }
@@ -1,5 +1,4 @@
-use std::collections::VecDeque;
-use std::fmt::Debug;
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -8,144 +7,358 @@ struct Rule {
     outcome: bool,
 }
 
-#[derive(Debug, PartialEq)]
-struct PotDeque {
-    pots: VecDeque<bool>,
-    zero_offset: i32,
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+impl FromStr for Rule {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (pattern, outcome) = s
+            .trim()
+            .split_once("=>")
+            .ok_or_else(|| format!("No `=>` in rule {:?}", s.trim()))?;
+        let pots = |s: &str| {
+            s.trim()
+                .chars()
+                .map(|c| match c {
+                    '#' => Ok(true),
+                    '.' => Ok(false),
+                    _ => Err(format!("Unexpected {:?} in rule {:?}", c, s.trim())),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+        let pattern = pots(pattern)?;
+        let outcome = match &pots(outcome)?[..] {
+            [outcome] => *outcome,
+            _ => return Err(format!("Rule {:?} must produce one pot", s.trim()).into()),
+        };
+        Ok(Rule { pattern, outcome })
+    }
+}
+
+/// Rules are looked up in a table this big, so windows can't be arbitrarily wide.
+const MAX_RADIUS: usize = 10;
+
+/// What each window of `2 * radius + 1` pots turns into, indexed by the window read as a binary
+/// number with the leftmost pot as its most significant bit.
+#[derive(Debug, PartialEq, Clone)]
+struct RuleTable {
+    radius: usize,
+    table: Vec<bool>,
 }
 
-impl PotDeque {
-    fn new(vd: &[bool]) -> Self {
-        PotDeque {
-            pots: Vec::from(vd).into(),
-            zero_offset: 0,
+impl RuleTable {
+    fn new(radius: usize) -> Result<Self> {
+        if radius > MAX_RADIUS {
+            return Err(format!("Radius {} is wider than {}", radius, MAX_RADIUS).into());
         }
+        Ok(RuleTable {
+            radius,
+            table: vec![false; 1 << (2 * radius + 1)],
+        })
     }
 
-    // https://www.reddit.com/r/rust/comments/bo053e/hey_rustaceans_got_an_easy_question_ask_here/enn75mx/
-    fn windows(
-        &self,
-        window_len: usize,
-    ) -> impl Debug + Iterator<Item = impl Debug + Clone + Iterator<Item = &bool>> {
-        let mut base_iter = self.pots.iter();
-        std::iter::from_fn(move || {
-            if base_iter.len() >= window_len {
-                // Clone the base iterator at its current position to make a window.
-                let window = base_iter.clone().take(window_len);
-                // Move the base iterator forward.
-                base_iter.next();
-                Some(window)
-            } else {
-                None
+    /// Builds a table from rules like `..#.# => #`, which must all be the same odd width. Windows
+    /// without a rule produce an empty pot.
+    fn from_rules(rules: &[Rule]) -> Result<Self> {
+        let width = rules.first().ok_or("No rules")?.pattern.len();
+        if width % 2 == 0 {
+            return Err(format!("Rules must be an odd number of pots wide, not {}", width).into());
+        }
+        let mut table = RuleTable::new(width / 2)?;
+        let mut seen = vec![false; table.table.len()];
+        for rule in rules {
+            if rule.pattern.len() != width {
+                return Err(format!(
+                    "Rules are {} pots wide, not all {}",
+                    rule.pattern.len(),
+                    width
+                )
+                .into());
             }
-        })
+            let index = rule
+                .pattern
+                .iter()
+                .fold(0, |index, &pot| index << 1 | usize::from(pot));
+            if seen[index] && table.table[index] != rule.outcome {
+                return Err(format!("Conflicting rules for {}", Pots(&rule.pattern)).into());
+            }
+            seen[index] = true;
+            table.table[index] = rule.outcome;
+        }
+        table.check()
     }
-}
 
-impl std::fmt::Display for PotDeque {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.pots
-                .iter()
-                .map(|&p| if p { '#' } else { '.' })
-                .collect::<String>()
-        )
+    /// Builds a table from a Wolfram code, whose bit `n` is what the window numbered `n` becomes.
+    fn from_wolfram(number: u128, radius: usize) -> Result<Self> {
+        let mut table = RuleTable::new(radius)?;
+        if table.table.len() < 128 && number >> table.table.len() != 0 {
+            return Err(format!("Rule {} is too big for radius {}", number, radius).into());
+        }
+        if table.table.len() > 128 {
+            return Err(format!("Wolfram codes only go up to radius 3, not {}", radius).into());
+        }
+        for (index, outcome) in table.table.iter_mut().enumerate() {
+            *outcome = number >> index & 1 == 1;
+        }
+        table.check()
     }
-}
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+    /// Only finitely many pots can ever hold plants, so empty pots must stay empty.
+    fn check(self) -> Result<Self> {
+        if self.table[0] {
+            return Err("Rules would fill every empty pot with plants".into());
+        }
+        Ok(self)
+    }
+}
 
-impl FromStr for Rule {
-    type Err = Box<dyn std::error::Error>;
+/// Shows pots as `#` and `.`.
+struct Pots<'a>(&'a [bool]);
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        let mut chars = s.trim().chars();
-        let pattern: Vec<_> = chars.by_ref().take(5).map(|c| c == '#').collect();
-        let outcome = chars
-            .nth(4)
-            .ok_or_else(|| Self::Err::from("No 5th character"))?
-            == '#';
-        Ok(Rule { pattern, outcome })
+impl std::fmt::Display for Pots<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let pots = self.0.iter().map(|&p| if p { '#' } else { '.' });
+        write!(f, "{}", pots.collect::<String>())
     }
 }
 
-fn parse_input(input: &str) -> Result<(PotDeque, Vec<Rule>)> {
-    let mut lines = input.lines();
-    let initial_state: Vec<bool> = lines
-        .next()
-        .expect("No first line")
-        .chars()
-        .skip(15)
-        .map(|c| c == '#')
-        .collect();
-    lines.next();
-    let rules = lines.map(str::parse).collect::<Result<Vec<_>>>()?;
-    Ok((PotDeque::new(&initial_state), rules))
+/// An endless row of pots, each either holding a plant or not, changed by a `RuleTable`.
+#[derive(Debug, PartialEq, Clone)]
+struct CellularAutomaton1D {
+    rules: RuleTable,
+    /// Bit `i % 64` of `cells[i / 64]` is pot number `start + i`. Every pot outside holds nothing.
+    cells: Vec<u64>,
+    start: i64,
+    generation: u64,
 }
 
-fn evolve(pot_deque: &mut PotDeque, rules: &[Rule]) {
-    let add_to_front = pot_deque.pots.iter().take(5).position(|&x| x);
-    if let Some(num) = add_to_front {
-        (0..(5 - num)).for_each(|_| {
-            pot_deque.pots.push_front(false);
-            pot_deque.zero_offset += 1;
-        });
+impl CellularAutomaton1D {
+    fn new(rules: RuleTable, initial: &[bool]) -> Self {
+        let mut cells = vec![0; initial.len().div_ceil(64)];
+        for (i, _pot) in initial.iter().enumerate().filter(|(_i, &pot)| pot) {
+            cells[i / 64] |= 1 << (i % 64);
+        }
+        CellularAutomaton1D {
+            rules,
+            cells,
+            start: 0,
+            generation: 0,
+        }
+    }
+
+    fn get(&self, pot: i64) -> bool {
+        let i = pot - self.start;
+        if i < 0 || i >= self.cells.len() as i64 * 64 {
+            return false;
+        }
+        let i = i as usize;
+        self.cells[i / 64] >> (i % 64) & 1 == 1
     }
 
-    let add_to_back = pot_deque.pots.iter().rev().take(5).position(|&x| x);
-    if let Some(num) = add_to_back {
-        (0..(5 - num)).for_each(|_| pot_deque.pots.push_back(false));
+    /// The first and last pots with plants in them.
+    fn bounds(&self) -> Option<(i64, i64)> {
+        let first = self.cells.iter().position(|&word| word != 0)?;
+        let last = self.cells.iter().rposition(|&word| word != 0)?;
+        Some((
+            self.start + first as i64 * 64 + i64::from(self.cells[first].trailing_zeros()),
+            self.start + last as i64 * 64 + 63 - i64::from(self.cells[last].leading_zeros()),
+        ))
     }
 
-    // Every iteration will lose 2 from the front due to the windows
-    pot_deque.zero_offset -= 2;
+    /// Moves on a generation. Plants can only spread `radius` pots either side of where they
+    /// are now, so only that stretch is worked out and stored.
+    fn step(&mut self) {
+        self.generation += 1;
+        let Some((first, last)) = self.bounds() else {
+            return;
+        };
+        let radius = self.rules.radius as i64;
+        let mask = self.rules.table.len() - 1;
+        let start = first - radius;
+        let len = (last - first + 2 * radius + 1) as usize;
 
-    pot_deque.pots = pot_deque
-        .windows(5)
-        .map(|window| {
-            if let Some(rule) = rules
-                .iter()
-                .find(|rule| rule.pattern.iter().eq(window.clone()))
-            {
-                rule.outcome
-            } else {
-                false
+        // Slide a window over the pots, shifting in the one at its right edge each time
+        let mut window = (start - 2 * radius..start)
+            .fold(0, |window, pot| window << 1 | usize::from(self.get(pot)));
+        let mut cells = vec![0; len.div_ceil(64)];
+        for i in 0..len {
+            let pot = start + i as i64;
+            window = (window << 1 | usize::from(self.get(pot + radius))) & mask;
+            if self.rules.table[window] {
+                cells[i / 64] |= 1 << (i % 64);
             }
+        }
+        self.cells = cells;
+        self.start = start;
+    }
+
+    /// The sum of the numbers of the pots with plants.
+    fn score(&self) -> i64 {
+        self.bounds().map_or(0, |(first, last)| {
+            (first..=last).filter(|&pot| self.get(pot)).sum()
         })
-        .collect::<VecDeque<_>>();
+    }
+
+    /// The first pot with a plant, and the plants from there on packed into words the same way
+    /// as `cells`, so that two rows that only differ by a shift have the same pattern.
+    fn pattern(&self) -> (i64, Vec<u64>) {
+        let Some((first, last)) = self.bounds() else {
+            return (0, Vec::new());
+        };
+        let len = (last - first + 1) as usize;
+        let mut pattern = vec![0; len.div_ceil(64)];
+        for i in (0..len).filter(|&i| self.get(first + i as i64)) {
+            pattern[i / 64] |= 1 << (i % 64);
+        }
+        (first, pattern)
+    }
 }
 
-fn score(pot_deque: &PotDeque) -> i32 {
-    let nums = -pot_deque.zero_offset..;
-    pot_deque
-        .pots
-        .iter()
-        .zip(nums)
-        .map(|(p, num)| if *p { num } else { 0 })
-        .sum()
+/// How many generations to search for a repeating pattern before giving up.
+const CYCLE_BUDGET: u64 = 10_000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Snapshot {
+    first: i64,
+    score: i64,
+    plants: i64,
 }
 
-fn part1(input: &str, generations: u64) -> Result<i64> {
-    let (mut pot_deque, rules) = parse_input(input)?;
-    let mut last_score = 0;
-    let mut score_: i64;
-    let mut diff = 0_i64;
-    for generation in 1..=generations {
-        evolve(&mut pot_deque, &rules);
+/// From generation `start` on, the pattern repeats every `period` generations, moved `drift`
+/// pots to the right each time.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Cycle {
+    start: u64,
+    period: u64,
+    drift: i64,
+}
 
-        score_ = score(&pot_deque).into();
-        if diff - (score_ - last_score) == 0 {
-            return Ok((generations - generation) as i64 * diff + score_);
+/// Scores of an automaton at any generation, worked out from the generations before its pattern
+/// starts repeating.
+#[derive(Debug)]
+struct Extrapolation {
+    /// The generation of `history[0]`
+    base: u64,
+    history: Vec<Snapshot>,
+    cycle: Option<Cycle>,
+    budget: u64,
+}
+
+impl Extrapolation {
+    /// Runs `pots` on until its pattern repeats, or for `budget` generations if it never does.
+    fn new(mut pots: CellularAutomaton1D, budget: u64) -> Self {
+        let base = pots.generation;
+        let mut seen = HashMap::new();
+        let mut history = Vec::new();
+        let mut cycle = None;
+        loop {
+            let (first, pattern) = pots.pattern();
+            let plants = pattern
+                .iter()
+                .map(|word| i64::from(word.count_ones()))
+                .sum();
+            history.push(Snapshot {
+                first,
+                score: pots.score(),
+                plants,
+            });
+            if let Some(&start) = seen.get(&pattern) {
+                let was: &Snapshot = &history[(start - base) as usize];
+                cycle = Some(Cycle {
+                    start,
+                    period: pots.generation - start,
+                    drift: first - was.first,
+                });
+                break;
+            }
+            if pots.generation - base == budget {
+                break;
+            }
+            seen.insert(pattern, pots.generation);
+            pots.step();
+        }
+        Extrapolation {
+            base,
+            history,
+            cycle,
+            budget,
         }
-        diff = score_ - last_score;
-        last_score = score_;
     }
-    Ok(i64::from(score(&pot_deque)))
+
+    /// The score at `generation`. Once the pattern repeats, each period moves every plant along
+    /// by the drift, adding `drift` to the score for each of them.
+    fn score(&self, generation: u64) -> Result<i64> {
+        let past = generation
+            .checked_sub(self.base)
+            .ok_or_else(|| format!("Generation {} was before {}", generation, self.base))?;
+        if let Some(snapshot) = self.history.get(past as usize) {
+            return Ok(snapshot.score);
+        }
+
+        let cycle = self
+            .cycle
+            .ok_or_else(|| format!("No repeating pattern within {} generations", self.budget))?;
+        let since = generation - cycle.start;
+        let periods = i64::try_from(since / cycle.period)?;
+        let snapshot = self.history[(cycle.start + since % cycle.period - self.base) as usize];
+        periods
+            .checked_mul(cycle.drift)
+            .and_then(|shift| shift.checked_mul(snapshot.plants))
+            .and_then(|gain| gain.checked_add(snapshot.score))
+            .ok_or_else(|| format!("The score at generation {} is too big", generation).into())
+    }
+}
+
+impl std::fmt::Display for CellularAutomaton1D {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.bounds() {
+            Some((first, last)) => {
+                let pots = (first..=last).map(|pot| self.get(pot)).collect::<Vec<_>>();
+                write!(f, "{}", Pots(&pots))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_input(input: &str) -> Result<(Vec<bool>, Vec<Rule>)> {
+    let mut lines = input.lines();
+    let initial_state: Vec<bool> = lines
+        .next()
+        .expect("No first line")
+        .chars()
+        .skip(15)
+        .map(|c| c == '#')
+        .collect();
+    lines.next();
+    let rules = lines.map(str::parse).collect::<Result<Vec<_>>>()?;
+    Ok((initial_state, rules))
+}
+
+fn part1(input: &str, generations: u64) -> Result<i64> {
+    let (initial_state, rules) = parse_input(input)?;
+    let pots = CellularAutomaton1D::new(RuleTable::from_rules(&rules)?, &initial_state);
+    Extrapolation::new(pots, CYCLE_BUDGET).score(generations)
 }
 
 fn main() -> Result<()> {
+    // Optionally draw an elementary automaton from a single plant instead, e.g.
+    // `day12 --wolfram 90 16`
+    let args = std::env::args().collect::<Vec<_>>();
+    if let [_, flag, number, generations] = &args[..] {
+        if flag == "--wolfram" {
+            let rules = RuleTable::from_wolfram(number.parse()?, 1)?;
+            let generations: i64 = generations.parse()?;
+            let mut pots = CellularAutomaton1D::new(rules, &[true]);
+            for _ in 0..=generations {
+                let (first, _last) = pots.bounds().unwrap_or((0, 0));
+                println!("{:>w$}{}", "", pots, w = (first + generations) as usize);
+                pots.step();
+            }
+            return Ok(());
+        }
+    }
 
     let input = std::fs::read_to_string("day12/input.txt")?;
     println!("part 1: {}", part1(&input, 20)?);
@@ -156,6 +369,24 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    const EXAMPLE: &str = "initial state: #..#.#..##......###...###
+
+        ...## => #
+        ..#.. => #
+        .#... => #
+        .#.#. => #
+        .#.## => #
+        .##.. => #
+        .#### => #
+        #.#.# => #
+        #.### => #
+        ##.#. => #
+        ##.## => #
+        ###.. => #
+        ###.# => #
+        ####. => #";
+
     #[test]
     fn test_parse_input() {
         let input = "initial state: #..#.#..##......###...###
@@ -166,11 +397,11 @@ mod tests {
         assert_eq!(
             parse_input(input).unwrap(),
             (
-                PotDeque::new(&[
+                vec![
                     true, false, false, true, false, true, false, false, true, true, false, false,
                     false, false, false, false, true, true, true, false, false, false, true, true,
                     true
-                ]),
+                ],
                 vec![
                     Rule {
                         pattern: vec![false, false, false, true, true],
@@ -190,23 +421,6 @@ mod tests {
     }
     #[test]
     fn test_part1() {
-        let input = "initial state: #..#.#..##......###...###
-
-        ...## => #
-        ..#.. => #
-        .#... => #
-        .#.#. => #
-        .#.## => #
-        .##.. => #
-        .#### => #
-        #.#.# => #
-        #.### => #
-        ##.#. => #
-        ##.## => #
-        ###.. => #
-        ###.# => #
-        ####. => #";
-
         //  0: ...#..#.#..##......###...###...........
         //  1: ...#...#....#.....#..#..#..#...........
         //  2: ...##..##...##....#..#..#..##..........
@@ -232,6 +446,126 @@ mod tests {
         // In this example, after 20 generations, the pots shown as # contain plants, the furthest
         // left of which is pot -2, and the furthest right of which is pot 34. Adding up all the
         // numbers of plant-containing pots after the 20th generation produces 325.
-        assert_eq!(part1(input, 20).unwrap(), 325)
+        assert_eq!(part1(EXAMPLE, 20).unwrap(), 325)
+    }
+
+    fn alive(table: &[bool]) -> Vec<usize> {
+        (0..table.len()).filter(|&i| table[i]).collect()
+    }
+
+    #[test]
+    fn test_rule_table() {
+        let rules = ["..#.# => #", "#.... => .", "##### => #"]
+            .iter()
+            .map(|r| r.parse())
+            .collect::<Result<Vec<Rule>>>()
+            .unwrap();
+        let table = RuleTable::from_rules(&rules).unwrap();
+        assert_eq!(table.radius, 2);
+        assert_eq!(alive(&table.table), vec![0b00101, 0b11111]);
+
+        let wide = ["...#... => #".parse().unwrap()];
+        assert_eq!(RuleTable::from_rules(&wide).unwrap().radius, 3);
+
+        for rules in [
+            &["..#.# => #", "..#.# => ."][..],
+            &["..#.# => #", "... => #"],
+            &["..#. => #"],
+            &["..... => #"],
+        ] {
+            let rules = rules
+                .iter()
+                .map(|r| r.parse().unwrap())
+                .collect::<Vec<Rule>>();
+            assert!(RuleTable::from_rules(&rules).is_err());
+        }
+        assert!("..#.# => ##".parse::<Rule>().is_err());
+        assert!("..x.# => #".parse::<Rule>().is_err());
+
+        let rule90 = RuleTable::from_wolfram(90, 1).unwrap();
+        assert_eq!(alive(&rule90.table), vec![1, 3, 4, 6]);
+        assert!(RuleTable::from_wolfram(256, 1).is_err());
+        assert!(RuleTable::from_wolfram(1, 1).is_err());
+        assert!(RuleTable::from_wolfram(2, 4).is_err());
+        assert!(RuleTable::new(MAX_RADIUS + 1).is_err());
+    }
+
+    #[test]
+    fn test_wolfram() {
+        // Rule 90 draws a Sierpinski triangle
+        let mut pots = CellularAutomaton1D::new(RuleTable::from_wolfram(90, 1).unwrap(), &[true]);
+        let mut rows = Vec::new();
+        for _ in 0..4 {
+            rows.push((pots.bounds().unwrap(), pots.to_string()));
+            pots.step();
+        }
+        assert_eq!(
+            rows,
+            vec![
+                ((0, 0), "#".to_string()),
+                ((-1, 1), "#.#".to_string()),
+                ((-2, 2), "#...#".to_string()),
+                ((-3, 3), "#.#.#.#".to_string()),
+            ]
+        );
+        assert_eq!(pots.generation, 4);
+
+        // Plants spread a word's width either way, across the edges of the bitset
+        let mut pots = CellularAutomaton1D::new(RuleTable::from_wolfram(254, 1).unwrap(), &[true]);
+        for _ in 0..100 {
+            pots.step();
+        }
+        assert_eq!(pots.bounds(), Some((-100, 100)));
+        assert_eq!(pots.score(), 0);
+        assert_eq!(pots.to_string(), "#".repeat(201));
+
+        // Everything can die off
+        let mut pots =
+            CellularAutomaton1D::new(RuleTable::from_wolfram(4, 1).unwrap(), &[true, true]);
+        pots.step();
+        assert_eq!(
+            (pots.bounds(), pots.score(), pots.to_string()),
+            (None, 0, String::new())
+        );
+    }
+
+    #[test]
+    fn test_extrapolation() {
+        // A glider moving one pot right every generation
+        let rules = RuleTable::from_wolfram(0b1111_0000, 1).unwrap();
+        let pots = CellularAutomaton1D::new(rules, &[true, false, true]);
+        let extrapolation = Extrapolation::new(pots.clone(), 100);
+        assert_eq!(
+            extrapolation.cycle,
+            Some(Cycle {
+                start: 0,
+                period: 1,
+                drift: 1
+            })
+        );
+        assert_eq!(extrapolation.score(0).unwrap(), 2);
+        assert_eq!(extrapolation.score(1_000_000).unwrap(), 2_000_002);
+
+        // Checked against running it out
+        let mut pots = CellularAutomaton1D::new(RuleTable::from_wolfram(90, 1).unwrap(), &[true]);
+        let extrapolation = Extrapolation::new(pots.clone(), 10);
+        assert_eq!(extrapolation.cycle, None);
+        for generation in 0..=10 {
+            assert_eq!(extrapolation.score(generation).unwrap(), pots.score());
+            pots.step();
+        }
+        assert!(extrapolation.score(11).is_err());
+
+        // The example settles into a pattern that moves right forever
+        let (initial_state, rules) = parse_input(EXAMPLE).unwrap();
+        let mut pots =
+            CellularAutomaton1D::new(RuleTable::from_rules(&rules).unwrap(), &initial_state);
+        let extrapolation = Extrapolation::new(pots.clone(), CYCLE_BUDGET);
+        let cycle = extrapolation.cycle.unwrap();
+        for generation in 0..cycle.start + 3 * cycle.period {
+            assert_eq!(extrapolation.score(generation).unwrap(), pots.score());
+            pots.step();
+        }
+        assert!(extrapolation.score(u64::MAX).is_err());
     }
 }
@@ -1,11 +1,10 @@
 // Part 1: 57,104
 // Part 2: 67,74
 use std::cmp::Ordering;
-use std::collections::HashMap;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Direction {
     Up,
     Down,
@@ -13,7 +12,7 @@ enum Direction {
     Right,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Turn {
     Left,
     Straight,
@@ -22,6 +21,7 @@ enum Turn {
 
 #[derive(Clone, Debug)]
 struct Cart {
+    id: usize,
     position: (usize, usize),
     direction: Direction,
     intersection_handler: std::iter::Cycle<std::vec::IntoIter<Turn>>,
@@ -49,9 +49,10 @@ impl PartialEq for Cart {
 }
 
 impl Cart {
-    fn new(position: (usize, usize), direction: char) -> Self {
+    fn new(id: usize, position: (usize, usize), direction: char) -> Self {
         use Turn::*;
         Cart {
+            id,
             position,
             direction: match direction {
                 '^' => Direction::Up,
@@ -63,15 +64,17 @@ impl Cart {
             intersection_handler: vec![Left, Straight, Right].into_iter().cycle(),
         }
     }
-    fn proceed(&mut self, grid: &Grid) {
+    /// Moves one step along the track, returning which way the cart went if it crossed an
+    /// intersection.
+    fn proceed(&mut self, track: &Track) -> Option<Turn> {
         match self.direction {
             Direction::Up => self.position.1 -= 1,
             Direction::Down => self.position.1 += 1,
             Direction::Left => self.position.0 -= 1,
             Direction::Right => self.position.0 += 1,
         };
-        match grid.get(&self.position) {
-            Some(GridPoint::Corner(corner)) => match (corner, &self.direction) {
+        match track.get(self.position) {
+            corner @ ('/' | '\\') => match (corner, &self.direction) {
                 ('/', Direction::Up) | ('\\', Direction::Down) => self.direction = Direction::Right,
                 ('/', Direction::Down) | ('\\', Direction::Up) => self.direction = Direction::Left,
                 ('/', Direction::Right) | ('\\', Direction::Left) => self.direction = Direction::Up,
@@ -80,8 +83,9 @@ impl Cart {
                 }
                 _ => panic!("Weird corner."),
             },
-            Some(GridPoint::Intersection) => {
-                match (self.intersection_handler.next().unwrap(), &self.direction) {
+            '+' => {
+                let turn = self.intersection_handler.next().unwrap();
+                match (turn, &self.direction) {
                     (Turn::Straight, _) => (),
                     (Turn::Right, Direction::Up) | (Turn::Left, Direction::Down) => {
                         self.direction = Direction::Right
@@ -96,95 +100,352 @@ impl Cart {
                         self.direction = Direction::Down
                     }
                 }
+                return Some(turn);
             }
-            None => (),
+            _ => (),
+        }
+        None
+    }
+
+    fn symbol(&self) -> char {
+        match self.direction {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
         }
     }
 }
 
-#[derive(Clone)]
-enum GridPoint {
-    Corner(char),
-    Intersection,
+const UP: u8 = 1;
+const RIGHT: u8 = 2;
+const DOWN: u8 = 4;
+const LEFT: u8 = 8;
+
+/// Every shape a piece of track can have, as the directions it leads off in.
+const SHAPES: [u8; 7] = [
+    LEFT | RIGHT,
+    UP | DOWN,
+    UP | RIGHT | DOWN | LEFT,
+    RIGHT | DOWN,
+    UP | LEFT,
+    LEFT | DOWN,
+    UP | RIGHT,
+];
+
+fn piece(shape: u8) -> char {
+    match shape {
+        0 => ' ',
+        s if s == LEFT | RIGHT => '-',
+        s if s == UP | DOWN => '|',
+        s if s == RIGHT | DOWN || s == UP | LEFT => '/',
+        s if s == LEFT | DOWN || s == UP | RIGHT => '\\',
+        _ => '+',
+    }
 }
 
-type Grid = HashMap<(usize, usize), GridPoint>;
+/// The map of the track, with every piece known, including those under the carts.
+#[derive(Clone, Debug, PartialEq)]
+struct Track {
+    pieces: Vec<Vec<char>>,
+}
 
-fn parse_input(input: &str) -> (Grid, Vec<Cart>) {
-    let mut grid: Grid = HashMap::new();
-    let mut carts: Vec<Cart> = Vec::new();
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            match c {
-                '^' | '<' | '>' | 'v' => {
-                    carts.push(Cart::new((x, y), c));
-                }
-                '+' => {
-                    grid.insert((x, y), GridPoint::Intersection);
-                }
-                '/' | '\\' => {
-                    grid.insert((x, y), GridPoint::Corner(c));
+impl Track {
+    /// Works out the track from a map, checking that every piece joins up with its neighbours.
+    ///
+    /// Corners could go either of two ways and the piece under a cart could be anything leading
+    /// the way it's going, so each square starts with every shape it might have. Shapes are then
+    /// ruled out until every square agrees with its neighbours about whether they are joined.
+    fn new(map: &[Vec<char>]) -> Result<Self> {
+        let height = map.len();
+        let width = map.iter().map(Vec::len).max().unwrap_or(0);
+        let mut shapes = vec![vec![vec![0]; width]; height];
+        for (y, row) in map.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                let leads = |direction: u8| {
+                    SHAPES
+                        .iter()
+                        .copied()
+                        .filter(|s| s & direction != 0)
+                        .collect()
+                };
+                shapes[y][x] = match c {
+                    ' ' => vec![0],
+                    '-' => vec![LEFT | RIGHT],
+                    '|' => vec![UP | DOWN],
+                    '+' => vec![UP | RIGHT | DOWN | LEFT],
+                    '/' => vec![RIGHT | DOWN, UP | LEFT],
+                    '\\' => vec![LEFT | DOWN, UP | RIGHT],
+                    '^' => leads(UP),
+                    '>' => leads(RIGHT),
+                    'v' => leads(DOWN),
+                    '<' => leads(LEFT),
+                    _ => return Err(format!("Unexpected {:?} at {},{}", c, x, y).into()),
+                };
+            }
+        }
+
+        // Off the edge of the map there is no track to join
+        let neighbour = |shapes: &[Vec<Vec<u8>>], x: usize, y: usize, direction: u8| {
+            let (x, y) = match direction {
+                UP => (Some(x), y.checked_sub(1)),
+                DOWN => (Some(x), Some(y + 1)),
+                LEFT => (x.checked_sub(1), Some(y)),
+                _ => (Some(x + 1), Some(y)),
+            };
+            match (x, y) {
+                (Some(x), Some(y)) if y < height && x < width => shapes[y][x].clone(),
+                _ => vec![0],
+            }
+        };
+        let opposite = |direction: u8| (direction << 2 | direction >> 2) & 0b1111;
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for y in 0..height {
+                for x in 0..width {
+                    let before = shapes[y][x].len();
+                    let possible = shapes[y][x]
+                        .iter()
+                        .copied()
+                        .filter(|&shape| {
+                            [UP, RIGHT, DOWN, LEFT].iter().all(|&direction| {
+                                let joined = shape & direction != 0;
+                                neighbour(&shapes, x, y, direction)
+                                    .iter()
+                                    .any(|n| (n & opposite(direction) != 0) == joined)
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    if possible.is_empty() {
+                        return Err(format!("Track doesn't join up at {},{}", x, y).into());
+                    }
+                    changed |= possible.len() != before;
+                    shapes[y][x] = possible;
                 }
-                _ => (),
             }
         }
+
+        let mut pieces = vec![vec![' '; width]; height];
+        for (y, row) in shapes.iter().enumerate() {
+            for (x, possible) in row.iter().enumerate() {
+                pieces[y][x] = match possible[..] {
+                    [shape] => piece(shape),
+                    _ => return Err(format!("Can't tell which track is at {},{}", x, y).into()),
+                };
+            }
+        }
+        Ok(Track { pieces })
+    }
+
+    fn get(&self, (x, y): (usize, usize)) -> char {
+        self.pieces
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(' ')
+    }
+}
+
+impl std::fmt::Display for Track {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", draw(&self.pieces))
     }
-    (grid, carts)
 }
 
-fn part1(grid: Grid, carts: &mut Vec<Cart>) -> (usize, usize) {
-    loop {
-        carts.sort();
-        for _ in 0..carts.len() {
-            let mut cart = carts.remove(0);
-            cart.proceed(&grid);
-            if carts.iter().any(|c| c == &cart) {
-                carts.retain(|c| *c != cart);
-                return cart.position;
-            } else {
-                carts.push(cart);
+fn draw(pieces: &[Vec<char>]) -> String {
+    let rows = pieces
+        .iter()
+        .map(|row| row.iter().collect::<String>().trim_end().to_string());
+    rows.collect::<Vec<_>>().join("\n")
+}
+
+fn parse_input(input: &str) -> Result<(Track, Vec<Cart>)> {
+    let map = input
+        .lines()
+        .map(|line| line.chars().collect())
+        .collect::<Vec<Vec<_>>>();
+    let mut carts: Vec<Cart> = Vec::new();
+    for (y, row) in map.iter().enumerate() {
+        for (x, &c) in row.iter().enumerate() {
+            if let '^' | '<' | '>' | 'v' = c {
+                carts.push(Cart::new(carts.len(), (x, y), c));
             }
         }
     }
+    Ok((Track::new(&map)?, carts))
 }
 
-fn part2(grid: Grid, carts: &mut Vec<Cart>) -> (usize, usize) {
-    while carts.len() > 1 {
-        carts.sort();
-        let mut counter = 0;
-        let len = carts.len();
-        while counter < len {
-            let mut cart = carts.remove(0);
-            cart.proceed(&grid);
-            if let Some(idx) = carts
-                .iter()
-                .enumerate()
-                .filter_map(|(i, c)| if c == &cart { Some(i) } else { None })
-                .next()
-            {
-                carts.remove(idx);
-
-                // Carts at the beginning of the list have not gotten to move yet, but if they are
-                // removed, the counter needs to account for them in terms of when to reset the
-                // loop. This determines whether or not the cart has moved so far this loop.
-                if idx < (len - counter - 1) {
-                    counter += 1;
+/// Something that happened during a tick, which are numbered from 1. Carts are known by their
+/// `id`, their place in reading order on the original map.
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    /// A cart crossed an intersection, going the way it did.
+    Turn {
+        tick: usize,
+        cart: usize,
+        position: (usize, usize),
+        turn: Turn,
+    },
+    /// `carts.0` ran into `carts.1`.
+    Collision {
+        tick: usize,
+        position: (usize, usize),
+        carts: (usize, usize),
+    },
+    /// A cart was taken off the track after a collision.
+    Removed {
+        tick: usize,
+        cart: usize,
+        position: (usize, usize),
+    },
+    /// Every other cart has been removed, leaving this one where it is at the end of the tick.
+    LastCart {
+        tick: usize,
+        cart: usize,
+        position: (usize, usize),
+    },
+}
+
+struct CartSimulator {
+    track: Track,
+    carts: Vec<Cart>,
+    tick: usize,
+    /// Where carts collided during the last tick
+    crashes: Vec<(usize, usize)>,
+}
+
+impl CartSimulator {
+    fn new(track: Track, carts: Vec<Cart>) -> Self {
+        CartSimulator {
+            track,
+            carts,
+            tick: 0,
+            crashes: Vec::new(),
+        }
+    }
+
+    /// Moves every cart once, top to bottom and then left to right, and says what happened.
+    /// Carts that collide are removed straight away, so they can't be hit again by carts moving
+    /// later in the same tick.
+    fn tick(&mut self) -> Vec<Event> {
+        self.tick += 1;
+        let tick = self.tick;
+        let had_carts = self.carts.len();
+        self.carts.sort();
+
+        let mut events = Vec::new();
+        self.crashes.clear();
+        let mut crashed = vec![false; self.carts.len()];
+        for i in 0..self.carts.len() {
+            if crashed[i] {
+                continue;
+            }
+            let cart = &mut self.carts[i];
+            if let Some(turn) = cart.proceed(&self.track) {
+                events.push(Event::Turn {
+                    tick,
+                    cart: cart.id,
+                    position: cart.position,
+                    turn,
+                });
+            }
+
+            let cart = &self.carts[i];
+            let hit =
+                (0..self.carts.len()).find(|&j| j != i && !crashed[j] && self.carts[j] == *cart);
+            if let Some(j) = hit {
+                crashed[i] = true;
+                crashed[j] = true;
+                self.crashes.push(cart.position);
+                events.push(Event::Collision {
+                    tick,
+                    position: cart.position,
+                    carts: (cart.id, self.carts[j].id),
+                });
+                for cart in [cart, &self.carts[j]] {
+                    events.push(Event::Removed {
+                        tick,
+                        cart: cart.id,
+                        position: cart.position,
+                    });
                 }
-            } else {
-                carts.push(cart);
             }
-            counter += 1;
         }
+
+        let mut crashed = crashed.into_iter();
+        self.carts.retain(|_cart| !crashed.next().unwrap());
+        if let [cart] = &self.carts[..] {
+            if had_carts > 1 {
+                events.push(Event::LastCart {
+                    tick,
+                    cart: cart.id,
+                    position: cart.position,
+                });
+            }
+        }
+        events
+    }
+
+    /// The track as it stands, with the carts on it and an `X` wherever carts collided during
+    /// the last tick.
+    fn render(&self) -> String {
+        let mut pieces = self.track.pieces.clone();
+        for cart in &self.carts {
+            pieces[cart.position.1][cart.position.0] = cart.symbol();
+        }
+        for &(x, y) in &self.crashes {
+            pieces[y][x] = 'X';
+        }
+        draw(&pieces)
+    }
+
+    /// Every event from here on, ticking until at most one cart is left.
+    fn events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        std::iter::from_fn(move || (self.carts.len() > 1).then(|| self.tick())).flatten()
     }
-    carts[0].position
 }
 
-fn main() -> Result<()> {
+/// Where the first collision happens.
+fn part1(track: Track, carts: Vec<Cart>) -> Option<(usize, usize)> {
+    CartSimulator::new(track, carts)
+        .events()
+        .find_map(|event| match event {
+            Event::Collision { position, .. } => Some(position),
+            _ => None,
+        })
+}
 
+/// Where the last cart is once all the others have collided.
+fn part2(track: Track, carts: Vec<Cart>) -> Option<(usize, usize)> {
+    CartSimulator::new(track, carts)
+        .events()
+        .find_map(|event| match event {
+            Event::LastCart { position, .. } => Some(position),
+            _ => None,
+        })
+}
+
+fn main() -> Result<()> {
     let input = std::fs::read_to_string("day13/input.txt")?;
-    let (grid, mut carts) = parse_input(&input);
-    let (part1x, part1y) = part1(grid.clone(), &mut carts.clone());
-    let (part2x, part2y) = part2(grid, &mut carts);
+    let (track, carts) = parse_input(&input)?;
+
+    // Optionally show the track after some ticks instead, e.g. `day13 --tick 100`
+    let args = std::env::args().collect::<Vec<_>>();
+    if let [_, flag, ticks] = &args[..] {
+        if flag == "--tick" {
+            let mut simulator = CartSimulator::new(track, carts);
+            for _ in 0..ticks.parse()? {
+                simulator.tick();
+            }
+            println!("{}", simulator.render());
+            return Ok(());
+        }
+    }
+
+    let (part1x, part1y) = part1(track.clone(), carts.clone()).ok_or("No carts collide")?;
+    let (part2x, part2y) = part2(track, carts).ok_or("No cart is left on its own")?;
     println!("part 1: {},{}", part1x, part1y);
     println!("part 2: {},{}", part2x, part2y);
     Ok(())
@@ -193,9 +454,8 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn test_part1() {
-        let track = r#"
+
+    const EXAMPLE: &str = r#"
 /->-\
 |   |  /----\
 | /-+--+-\  |
@@ -203,13 +463,16 @@ mod tests {
 \-+-/  \-+--/
   \------/
 "#;
-        let input = track
-            .lines()
-            .filter(|l| !l.is_empty())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let (grid, mut carts) = parse_input(&input);
-        assert_eq!(part1(grid, &mut carts), (7, 3));
+
+    /// Parses a track written out with blank lines around it, so it can line up in the source.
+    fn parse(track: &str) -> (Track, Vec<Cart>) {
+        parse_input(track.trim_matches('\n')).unwrap()
+    }
+
+    #[test]
+    fn test_part1() {
+        let (track, carts) = parse(EXAMPLE);
+        assert_eq!(part1(track, carts), Some((7, 3)));
     }
 
     #[test]
@@ -223,12 +486,93 @@ mod tests {
   |   ^
   \<->/
 "#;
-        let input = track
-            .lines()
-            .filter(|l| !l.is_empty())
-            .collect::<Vec<_>>()
-            .join("\n");
-        let (grid, mut carts) = parse_input(&input);
-        assert_eq!(part2(grid, &mut carts), (6, 4));
+        let (track, carts) = parse(track);
+        assert_eq!(part2(track, carts), Some((6, 4)));
+    }
+
+    #[test]
+    fn test_events() {
+        let (track, carts) = parse(EXAMPLE);
+        let mut simulator = CartSimulator::new(track, carts);
+        let events = simulator.events().collect::<Vec<_>>();
+        assert_eq!(simulator.tick, 14);
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| matches!(e, Event::Turn { .. }))
+                .count(),
+            5
+        );
+        assert_eq!(
+            events[events.len() - 3..],
+            [
+                Event::Collision {
+                    tick: 14,
+                    position: (7, 3),
+                    carts: (0, 1)
+                },
+                Event::Removed {
+                    tick: 14,
+                    cart: 0,
+                    position: (7, 3)
+                },
+                Event::Removed {
+                    tick: 14,
+                    cart: 1,
+                    position: (7, 3)
+                },
+            ]
+        );
+        // Neither cart was left on its own, as they crashed together
+        assert!(simulator.carts.is_empty());
+        assert_eq!(simulator.tick(), vec![]);
+    }
+
+    #[test]
+    fn test_track() {
+        let parse = |map: &str| parse_input(map).map(|(track, _carts)| track.to_string());
+        assert_eq!(parse("/-v\n| |\n\\-/").unwrap(), "/-\\\n| |\n\\-/");
+        let map = "/---\\\n|   |\n| /-<-\\\n| | | |\n\\-+-/ |\n  \\---/";
+        assert_eq!(parse(map).unwrap(), map.replace('<', "+"));
+
+        assert_eq!(
+            parse("/-\\\n| |\n\\--").unwrap_err().to_string(),
+            "Track doesn't join up at 2,1"
+        );
+        assert_eq!(
+            parse("/-\\\n|?|\n\\-/").unwrap_err().to_string(),
+            "Unexpected '?' at 1,1"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let (track, carts) = parse(EXAMPLE);
+        let mut simulator = CartSimulator::new(track, carts);
+        assert_eq!(simulator.render(), EXAMPLE.trim_matches('\n'));
+
+        simulator.tick();
+        assert_eq!(
+            simulator.render().lines().take(4).collect::<Vec<_>>(),
+            vec![
+                "/-->\\",
+                "|   |  /----\\",
+                "| /-+--+-\\  |",
+                "| | |  | |  |"
+            ]
+        );
+
+        for _ in 1..14 {
+            simulator.tick();
+        }
+        let expected = r#"
+/---\
+|   |  /----\
+| /-+--+-\  |
+| | |  X |  |
+\-+-/  \-+--/
+  \------/
+"#;
+        assert_eq!(simulator.render(), expected.trim_matches('\n'));
     }
 }
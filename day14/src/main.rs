@@ -1,90 +1,201 @@
-use std::char;
-use std::collections::VecDeque;
-
-fn part1(input: u32) -> String {
-    let (mut first_elf, mut second_elf) = ((0_usize, 3_u8), (1_usize, 7_u8));
-    let mut full_recipe = vec![
-        char::from_digit(first_elf.1.into(), 10).unwrap(),
-        char::from_digit(second_elf.1.into(), 10).unwrap(),
-    ];
-
-    loop {
-        let new_recipe = first_elf.1 + second_elf.1;
-        full_recipe.extend(new_recipe.to_string().chars());
-
-        let len = full_recipe.len();
-        if len as u32 >= input + 10 {
-            return full_recipe
-                .split_off(input as usize)
-                .into_iter()
-                .take(10)
-                .collect();
-        }
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-        let first_elf_idx = (first_elf.0 + first_elf.1 as usize + 1) % full_recipe.len();
-        let second_elf_idx = (second_elf.0 + second_elf.1 as usize + 1) % full_recipe.len();
-        first_elf = (
-            first_elf_idx,
-            char::to_digit(full_recipe[first_elf_idx], 10).unwrap() as u8,
-        );
-        second_elf = (
-            second_elf_idx,
-            char::to_digit(full_recipe[second_elf_idx], 10).unwrap() as u8,
-        );
+/// How the elves' current scores, one per elf, make the next recipes.
+type Combine = fn(&[u8]) -> u64;
+
+/// The puzzle's rule: add the scores up.
+fn sum(scores: &[u8]) -> u64 {
+    scores.iter().map(|&s| u64::from(s)).sum()
+}
+
+/// Multiplies the scores instead, saturating at `u64::MAX` rather than overflowing.
+fn product(scores: &[u8]) -> u64 {
+    scores
+        .iter()
+        .fold(1, |acc: u64, &s| acc.saturating_mul(u64::from(s)))
+}
+
+#[derive(Clone, Debug)]
+struct ScoreboardConfig {
+    /// The first recipes, one elf starting on each.
+    starting_scores: Vec<u8>,
+    combine: Combine,
+    /// New recipes are the digits of the combined score in this base.
+    base: u8,
+}
+
+impl Default for ScoreboardConfig {
+    fn default() -> Self {
+        ScoreboardConfig {
+            starting_scores: vec![3, 7],
+            combine: sum,
+            base: 10,
+        }
     }
 }
 
-fn part2(input: &str) -> usize {
-    let (mut first_elf, mut second_elf) = ((0_usize, 3_u8), (1_usize, 7_u8));
-    let mut full_recipe = vec![
-        char::from_digit(first_elf.1.into(), 10).unwrap(),
-        char::from_digit(second_elf.1.into(), 10).unwrap(),
-    ];
-    let mut check_queue = VecDeque::new();
-    let start_char = input.chars().next().unwrap();
-
-    loop {
-        let new_recipe: Vec<_> = (first_elf.1 + second_elf.1).to_string().chars().collect();
-        if new_recipe.contains(&start_char) {
-            check_queue.push_back(full_recipe.len() - 1);
+/// The recipes made so far, and which one each elf is working from.
+#[derive(Clone, Debug)]
+struct Scoreboard {
+    scores: Vec<u8>,
+    elves: Vec<usize>,
+    combine: Combine,
+    base: u8,
+    /// Scratch space for the scores the elves are on
+    current: Vec<u8>,
+    /// Scratch space for the digits of each combined score
+    digits: Vec<u8>,
+}
+
+impl Scoreboard {
+    fn new(config: &ScoreboardConfig) -> Result<Self> {
+        let elves = config.starting_scores.len();
+        if elves == 0 {
+            return Err("There must be at least one elf".into());
+        }
+        if config.base < 2 {
+            return Err(format!("Scores can't be written in base {}", config.base).into());
+        }
+        if let Some(score) = config.starting_scores.iter().find(|&&s| s >= config.base) {
+            return Err(format!("{} isn't a digit in base {}", score, config.base).into());
         }
 
-        full_recipe.extend(new_recipe);
-
-        if let Some(idx) = check_queue.get(0) {
-            if full_recipe.len().saturating_sub(input.len()) > *idx {
-                {
-                    check_queue.pop_front();
-
-                    if full_recipe
-                        .get(full_recipe.len() - input.len() - 2..)
-                        .unwrap()
-                        .iter()
-                        .collect::<String>()
-                        .contains(input)
-                    {
-                        return full_recipe.iter().collect::<String>().find(input).unwrap();
-                    }
-                }
+        Ok(Scoreboard {
+            scores: config.starting_scores.clone(),
+            elves: (0..elves).collect(),
+            combine: config.combine,
+            base: config.base,
+            current: Vec::with_capacity(elves),
+            digits: Vec::with_capacity(64),
+        })
+    }
+
+    /// Makes the next recipes and moves the elves on.
+    fn step(&mut self) {
+        self.current.clear();
+        self.current
+            .extend(self.elves.iter().map(|&elf| self.scores[elf]));
+        let mut combined = (self.combine)(&self.current);
+
+        // Digits come out least significant first
+        let base = u64::from(self.base);
+        self.digits.clear();
+        loop {
+            self.digits.push((combined % base) as u8);
+            combined /= base;
+            if combined == 0 {
+                break;
             }
         }
+        self.scores.extend(self.digits.iter().rev());
 
-        let first_elf_idx = (first_elf.0 + first_elf.1 as usize + 1) % full_recipe.len();
-        let second_elf_idx = (second_elf.0 + second_elf.1 as usize + 1) % full_recipe.len();
-        first_elf = (
-            first_elf_idx,
-            char::to_digit(full_recipe[first_elf_idx], 10).unwrap() as u8,
-        );
-        second_elf = (
-            second_elf_idx,
-            char::to_digit(full_recipe[second_elf_idx], 10).unwrap() as u8,
-        );
+        for elf in self.elves.iter_mut() {
+            *elf = (*elf + 1 + usize::from(self.scores[*elf])) % self.scores.len();
+        }
+    }
+
+    /// Every score, from the first recipe on, making more recipes as needed.
+    fn scores(mut self) -> impl Iterator<Item = u8> {
+        let mut next = 0;
+        std::iter::from_fn(move || {
+            while next >= self.scores.len() {
+                self.step();
+            }
+            next += 1;
+            Some(self.scores[next - 1])
+        })
+    }
+}
+
+impl Default for Scoreboard {
+    fn default() -> Self {
+        Scoreboard::new(&ScoreboardConfig::default()).expect("The puzzle's rules are valid")
+    }
+}
+
+/// Where `pattern` first turns up in `haystack`, by Knuth-Morris-Pratt, which looks at each item
+/// just once so `haystack` can be streamed.
+fn find<T: PartialEq>(pattern: &[T], haystack: impl IntoIterator<Item = T>) -> Option<usize> {
+    if pattern.is_empty() {
+        return Some(0);
     }
+
+    // `fallback[i]` is the length of the longest proper prefix of `pattern[..=i]` that is also a
+    // suffix of it, so how much of a match survives a mismatch after `i + 1` items
+    let mut fallback = vec![0; pattern.len()];
+    let mut len = 0;
+    for i in 1..pattern.len() {
+        while len > 0 && pattern[i] != pattern[len] {
+            len = fallback[len - 1];
+        }
+        if pattern[i] == pattern[len] {
+            len += 1;
+        }
+        fallback[i] = len;
+    }
+
+    let mut matched = 0;
+    for (i, item) in haystack.into_iter().enumerate() {
+        while matched > 0 && item != pattern[matched] {
+            matched = fallback[matched - 1];
+        }
+        if item == pattern[matched] {
+            matched += 1;
+        }
+        if matched == pattern.len() {
+            return Some(i + 1 - pattern.len());
+        }
+    }
+    None
+}
+
+/// The ten scores after the first `input` recipes.
+fn part1(input: usize) -> String {
+    Scoreboard::default()
+        .scores()
+        .skip(input)
+        .take(10)
+        .map(|score| char::from(b'0' + score))
+        .collect()
+}
+
+/// How many recipes come before the scores spell out `input`.
+fn part2(input: &str) -> usize {
+    let pattern = input
+        .chars()
+        .map(|c| c.to_digit(10).expect("Scores are digits") as u8)
+        .collect::<Vec<_>>();
+    find(&pattern, Scoreboard::default().scores()).expect("The scores never end")
 }
 
-fn main() {
+fn main() -> Result<()> {
+    // Pass `--rules COMBINE BASE SCORES...` to see how the scores go under other rules instead,
+    // e.g. `--rules product 10 3 7 1` for three elves multiplying their scores
+    let mut args = std::env::args().skip_while(|arg| arg != "--rules").skip(1);
+    if let Some(combine) = args.next() {
+        let base = args
+            .next()
+            .ok_or("--rules needs a base after the way to combine")?;
+        let config = ScoreboardConfig {
+            combine: match combine.as_str() {
+                "sum" => sum,
+                "product" => product,
+                _ => return Err(format!("Unknown way to combine scores: {}", combine).into()),
+            },
+            base: base.parse()?,
+            starting_scores: args
+                .map(|s| s.parse())
+                .collect::<std::result::Result<_, _>>()?,
+        };
+        let scores = Scoreboard::new(&config)?.scores().take(80);
+        let scores = scores.map(|s| char::from_digit(s.into(), 36).unwrap_or('?'));
+        println!("{}", scores.collect::<String>());
+        return Ok(());
+    }
+
     println!("part 1: {}", part1(846_021));
     println!("part 2: {}", part2("846021"));
+    Ok(())
 }
 
 #[cfg(test)]
@@ -105,4 +216,87 @@ mod tests {
         assert_eq!(part2("92510"), 18);
         assert_eq!(part2("59414"), 2018);
     }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find(b"ababd", *b"abababd"), Some(2));
+        assert_eq!(find(b"aab", *b"aaab"), Some(1));
+        assert_eq!(find(b"", *b"abc"), Some(0));
+        assert_eq!(find(b"abc", *b"ab"), None);
+        assert_eq!(
+            find(&[7, 7], (0..).map(|i| if i < 7000 { i % 7 } else { 7 })),
+            Some(7000)
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let first = |config: ScoreboardConfig, n| {
+            Scoreboard::new(&config)
+                .unwrap()
+                .scores()
+                .take(n)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            first(ScoreboardConfig::default(), 10),
+            vec![3, 7, 1, 0, 1, 0, 1, 2, 4, 5]
+        );
+
+        // 3 * 7 = 21, after which both elves land back where they started
+        let multiply = ScoreboardConfig {
+            combine: product,
+            ..ScoreboardConfig::default()
+        };
+        assert_eq!(first(multiply, 6), vec![3, 7, 2, 1, 2, 1]);
+
+        // Any other rule will do, such as only counting the highest score
+        let highest = ScoreboardConfig {
+            combine: |scores| u64::from(*scores.iter().max().unwrap()),
+            ..ScoreboardConfig::default()
+        };
+        assert_eq!(first(highest, 6), vec![3, 7, 7, 7, 7, 7]);
+
+        // Products too big for a `u64` saturate rather than overflow
+        let huge = ScoreboardConfig {
+            starting_scores: vec![9; 30],
+            combine: product,
+            base: 10,
+        };
+        let saturated = u64::MAX.to_string();
+        let digits = saturated.bytes().map(|b| b - b'0');
+        assert!(first(huge, 50).into_iter().skip(30).eq(digits));
+
+        // 1 + 1 + 0 = 0b10, and then 0 + 1 + 1 = 0b10 again
+        let binary = ScoreboardConfig {
+            starting_scores: vec![1, 1, 0],
+            base: 2,
+            ..ScoreboardConfig::default()
+        };
+        assert_eq!(first(binary, 7), vec![1, 1, 0, 1, 0, 1, 0]);
+
+        // 3 + 7 + 1 = 11, and then all three elves land on the last 1
+        let three = ScoreboardConfig {
+            starting_scores: vec![3, 7, 1],
+            ..ScoreboardConfig::default()
+        };
+        assert_eq!(first(three, 6), vec![3, 7, 1, 1, 1, 3]);
+
+        for config in [
+            ScoreboardConfig {
+                starting_scores: vec![],
+                ..ScoreboardConfig::default()
+            },
+            ScoreboardConfig {
+                base: 1,
+                ..ScoreboardConfig::default()
+            },
+            ScoreboardConfig {
+                base: 7,
+                ..ScoreboardConfig::default()
+            },
+        ] {
+            assert!(Scoreboard::new(&config).is_err());
+        }
+    }
 }
@@ -2,26 +2,88 @@ use std::collections::VecDeque;
 use std::rc::Rc;
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Clone)]
-enum Species {
-    Goblin,
-    Elf,
+/// A faction, known by the letter it's drawn with. Every species is the enemy
+/// of every other.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Species(char);
+
+impl Species {
+    const GOBLIN: Species = Species('G');
+    const ELF: Species = Species('E');
 }
 
 #[derive(Debug, Clone, PartialEq)]
 struct Combatant {
     species: Species,
-    hit_points: u8,
-    attack_power: u8,
+    hit_points: u32,
+    attack_power: u32,
 }
 
-impl Combatant {
-    fn new(species: Species) -> Self {
-        Combatant {
-            species,
-            hit_points: 200,
-            attack_power: 3,
+/// What each species starts a fight with.
+#[derive(Debug, Clone, PartialEq)]
+struct SpeciesConfig {
+    species: Species,
+    hit_points: u32,
+    attack_power: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CombatConfig {
+    species: Vec<SpeciesConfig>,
+    /// The species part 2 raises the attack power of until none of it dies
+    boosted: Species,
+}
+
+impl Default for CombatConfig {
+    fn default() -> Self {
+        let species =
+            [Species::GOBLIN, Species::ELF].map(|species| SpeciesConfig {
+                species,
+                hit_points: 200,
+                attack_power: 3,
+            });
+        CombatConfig::new(species.to_vec(), Species::ELF)
+            .expect("the default species are distinct")
+    }
+}
+
+impl CombatConfig {
+    /// Checks that every species can be told apart on the board, and that the
+    /// boosted species is one of them
+    fn new(
+        species: Vec<SpeciesConfig>,
+        boosted: Species,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        for (i, config) in species.iter().enumerate() {
+            let symbol = config.species.0;
+            if symbol == '#' || symbol == '.' {
+                return Err(
+                    format!("species can't be drawn as {:?}", symbol).into()
+                );
+            }
+            if species[..i].iter().any(|other| other.species.0 == symbol) {
+                return Err(format!(
+                    "species {:?} is configured twice",
+                    symbol
+                )
+                .into());
+            }
+        }
+        if !species.iter().any(|config| config.species == boosted) {
+            return Err(format!("no species {:?} to boost", boosted.0).into());
         }
+        Ok(CombatConfig { species, boosted })
+    }
+
+    fn combatant(&self, symbol: char) -> Option<Combatant> {
+        self.species
+            .iter()
+            .find(|config| config.species.0 == symbol)
+            .map(|config| Combatant {
+                species: config.species,
+                hit_points: config.hit_points,
+                attack_power: config.attack_power,
+            })
     }
 }
 
@@ -44,7 +106,7 @@ struct PathNode {
 }
 
 impl PathNode {
-    fn iter(&self) -> Iter {
+    fn iter(&self) -> Iter<'_> {
         Iter { next: Some(self) }
     }
 
@@ -72,17 +134,20 @@ impl std::fmt::Debug for PathNode {
 }
 
 impl Board {
+    /// The game is over once only one species is left standing
     fn game_is_over(&self) -> bool {
-        ![Species::Goblin, Species::Elf].iter().all(|species| {
-            self.pieces.iter().flatten().any(|piece| match piece {
-                BoardPiece::Combatant(Combatant { species: s, .. })
-                    if s == species =>
-                {
-                    true
-                }
-                _ => false,
-            })
-        })
+        let mut species =
+            self.pieces
+                .iter()
+                .flatten()
+                .filter_map(|piece| match piece {
+                    BoardPiece::Combatant(c) => Some(c.species),
+                    _ => None,
+                });
+        match species.next() {
+            Some(first) => species.all(|s| s == first),
+            None => true,
+        }
     }
 
     /// Get the coordinates of squares adjacent to `coords`, minding the edges of the board
@@ -106,20 +171,19 @@ impl Board {
         adjacent
     }
 
-    /// Returns coordinates of best adjacent target (if any), preferring those with lowest hit
-    /// points left, and then in reading order
+    /// Returns coordinates of best adjacent target (if any) of a species other
+    /// than `species`, preferring those with lowest hit points left, and then in
+    /// reading order
     fn find_adjacent_enemy(
         &self,
-        enemy_species: &Species,
+        species: &Species,
         from: (usize, usize),
     ) -> Option<(usize, usize)> {
         let adjacent_enemies: Vec<_> = self
             .jiggle(from)
             .into_iter()
             .filter_map(|coord| match self.get(coord) {
-                Some(BoardPiece::Combatant(c))
-                    if c.species == *enemy_species =>
-                {
+                Some(BoardPiece::Combatant(c)) if c.species != *species => {
                     Some((c.hit_points, coord))
                 }
                 _ => None,
@@ -174,18 +238,18 @@ impl Board {
         };
         let other_piece = self.get_mut(enemy_coords).unwrap();
         if let BoardPiece::Combatant(enemy) = other_piece {
-            // Use +1 to adjust for case of `0`
-            if let Some(p) = enemy.hit_points.checked_sub(attack_power + 1) {
-                enemy.hit_points = p + 1;
+            if enemy.hit_points > attack_power {
+                enemy.hit_points -= attack_power;
             } else {
                 *other_piece = BoardPiece::Space;
             }
         }
     }
 
+    /// Finds the first step towards the nearest enemy of `species`
     fn find_enemy_path(
         &self,
-        enemy_species: &Species,
+        species: &Species,
         from: (usize, usize),
     ) -> Option<(usize, usize)> {
         let mut paths = VecDeque::from(vec![Rc::new(PathNode {
@@ -211,7 +275,7 @@ impl Board {
                             Some(BoardPiece::Combatant(Combatant {
                                 species: s,
                                 ..
-                            })) if s == enemy_species => {
+                            })) if s != species => {
                                 should_end = true;
                                 paths.push_back(Rc::new(PathNode {
                                     coords: pos,
@@ -239,7 +303,7 @@ impl Board {
             .into_iter()
             .filter(|p| matches!(self.get(p.coords), Some(BoardPiece::Combatant(Combatant {
                     species: s, ..
-                })) if s == enemy_species))
+                })) if s != species))
             .collect();
 
         if let Some(min_length) = paths.iter().map(|p| p.iter().count()).min()
@@ -266,7 +330,7 @@ impl Board {
         pieces_in_order: &mut VecDeque<((usize, usize), Combatant)>,
     ) {
         if let Some(BoardPiece::Combatant(c)) = self.get(enemy_coords) {
-            if let Some(mut p) = pieces_in_order
+            if let Some(p) = pieces_in_order
                 .iter_mut()
                 .find(|(coords, _)| coords == &enemy_coords)
             {
@@ -286,9 +350,8 @@ struct Iter<'a> {
 impl<'a> Iterator for Iter<'a> {
     type Item = &'a PathNode;
     fn next(&mut self) -> Option<Self::Item> {
-        self.next.take().map(|n| {
+        self.next.take().inspect(|n| {
             self.next = n.prev.as_deref();
-            n
         })
     }
 }
@@ -322,20 +385,10 @@ impl std::fmt::Display for Board {
             .iter()
             .map(|y| {
                 y.iter()
-                    .map(|p| {
-                        use BoardPiece::*;
-                        match p {
-                            Space => '.',
-                            Wall => '#',
-                            Combatant(crate::Combatant {
-                                species: Species::Goblin,
-                                ..
-                            }) => 'G',
-                            Combatant(crate::Combatant {
-                                species: Species::Elf,
-                                ..
-                            }) => 'E',
-                        }
+                    .map(|p| match p {
+                        BoardPiece::Space => '.',
+                        BoardPiece::Wall => '#',
+                        BoardPiece::Combatant(c) => c.species.0,
                     })
                     .collect::<String>()
             })
@@ -344,20 +397,21 @@ impl std::fmt::Display for Board {
     }
 }
 
-impl FromStr for Board {
-    type Err = Box<dyn std::error::Error>;
-    fn from_str(input: &str) -> Result<Board, Self::Err> {
+impl Board {
+    /// Reads a board whose combatants are drawn with the species in `config`
+    fn with_config(
+        input: &str,
+        config: &CombatConfig,
+    ) -> Result<Board, Box<dyn std::error::Error>> {
         let mut rows = Vec::new();
         for line in input.lines() {
             let mut columns = Vec::new();
             for c in line.chars() {
                 use BoardPiece::*;
-                use Species::*;
-                let piece = match c {
-                    '#' => Wall,
-                    '.' => Space,
-                    'G' => Combatant(crate::Combatant::new(Goblin)),
-                    'E' => Combatant(crate::Combatant::new(Elf)),
+                let piece = match (c, config.combatant(c)) {
+                    ('#', _) => Wall,
+                    ('.', _) => Space,
+                    (_, Some(combatant)) => Combatant(combatant),
                     _ => {
                         return Err(Box::new(std::io::Error::new(
                             std::io::ErrorKind::InvalidData,
@@ -379,6 +433,13 @@ impl FromStr for Board {
     }
 }
 
+impl FromStr for Board {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(input: &str) -> Result<Board, Self::Err> {
+        Board::with_config(input, &CombatConfig::default())
+    }
+}
+
 fn sort_reading_order<T, U>(paths: &mut [T])
 where
     T: AsRef<[(U, U)]>,
@@ -391,8 +452,11 @@ where
     });
 }
 
-fn part1(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
-    let mut board: Board = input.parse()?;
+fn part1(
+    input: &str,
+    config: &CombatConfig,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    let mut board = Board::with_config(input, config)?;
     for round in 0.. {
         // println!("Round: {}\n{}", round, board.to_string());
         let mut pieces_in_order = board
@@ -425,17 +489,13 @@ fn part1(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                             None
                         }
                     })
-                    .map(|p| u32::from(p.hit_points))
+                    .map(|p| p.hit_points)
                     .sum::<u32>();
                 return Ok(hit_points * round);
             }
 
-            let enemy_species = match piece.species {
-                Species::Elf => Species::Goblin,
-                Species::Goblin => Species::Elf,
-            };
             if let Some(enemy_coords) =
-                board.find_adjacent_enemy(&enemy_species, coords)
+                board.find_adjacent_enemy(&piece.species, coords)
             {
                 board.attack(coords, enemy_coords);
                 board.update_pieces(enemy_coords, &mut pieces_in_order);
@@ -445,7 +505,7 @@ fn part1(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                     // If piece hasn't gone yet it should still be in pieces_in_order (not yet
                     // popped off), and it may get killed before its turn, so need to update value
                     // in pieces_in_order by cloning from value in board, which should be updated
-                    if let Some(mut p) = pieces_in_order
+                    if let Some(p) = pieces_in_order
                         .iter_mut()
                         .find(|(coords, _)| coords == &enemy_coords)
                     {
@@ -457,13 +517,14 @@ fn part1(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                         .retain(|&(coords, _)| coords != enemy_coords);
                 }
             } else if let Some(next_step) =
-                board.find_enemy_path(&enemy_species, coords)
+                board.find_enemy_path(&piece.species, coords)
             {
+                let species = piece.species;
                 *board.get_mut(next_step).unwrap() =
                     BoardPiece::Combatant(piece);
                 *board.get_mut(coords).unwrap() = BoardPiece::Space;
                 if let Some(enemy_coords) =
-                    board.find_adjacent_enemy(&enemy_species, next_step)
+                    board.find_adjacent_enemy(&species, next_step)
                 {
                     board.attack(next_step, enemy_coords);
                     board.update_pieces(enemy_coords, &mut pieces_in_order);
@@ -471,22 +532,28 @@ fn part1(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
             }
         }
     }
-    Err(Box::new(std::io::Error::new(
-        std::io::ErrorKind::Other,
+    Err(Box::new(std::io::Error::other(
         "This should be unreachable code",
     )))
 }
 
-fn part2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
-    'outer: for power in 3..255 {
-        let mut board: Board = input.parse()?;
+fn part2(
+    input: &str,
+    config: &CombatConfig,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    let boosted = config.combatant(config.boosted.0).unwrap();
+    // Once every hit kills, more attack power can't change anything
+    let most_hit_points = config.species.iter().map(|s| s.hit_points).max();
+    let strongest = most_hit_points.unwrap_or(0).max(boosted.attack_power);
+    'outer: for power in boosted.attack_power..=strongest {
+        let mut board = Board::with_config(input, config)?;
         let mut elf_count = 0;
         board
             .pieces
             .iter_mut()
             .flatten()
             .for_each(|piece| match piece {
-                BoardPiece::Combatant(c) if c.species == Species::Elf => {
+                BoardPiece::Combatant(c) if c.species == config.boosted => {
                     c.attack_power = power;
                     elf_count += 1;
                 }
@@ -512,7 +579,7 @@ fn part2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                 .collect::<VecDeque<_>>();
             if pieces_in_order
                 .iter()
-                .filter(|(_, combatant)| combatant.species == Species::Elf)
+                .filter(|(_, combatant)| combatant.species == config.boosted)
                 .count()
                 != elf_count
             {
@@ -534,17 +601,13 @@ fn part2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                                 None
                             }
                         })
-                        .map(|p| u32::from(p.hit_points))
+                        .map(|p| p.hit_points)
                         .sum::<u32>();
                     return Ok(hit_points * round);
                 }
 
-                let enemy_species = match piece.species {
-                    Species::Elf => Species::Goblin,
-                    Species::Goblin => Species::Elf,
-                };
                 if let Some(enemy_coords) =
-                    board.find_adjacent_enemy(&enemy_species, coords)
+                    board.find_adjacent_enemy(&piece.species, coords)
                 {
                     board.attack(coords, enemy_coords);
                     board.update_pieces(enemy_coords, &mut pieces_in_order);
@@ -555,7 +618,7 @@ fn part2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                         // If piece hasn't gone yet it should still be in pieces_in_order (not yet
                         // popped off), and it may get killed before its turn, so need to update value
                         // in pieces_in_order by cloning from value in board, which should be updated
-                        if let Some(mut p) = pieces_in_order
+                        if let Some(p) = pieces_in_order
                             .iter_mut()
                             .find(|(coords, _)| coords == &enemy_coords)
                         {
@@ -567,13 +630,14 @@ fn part2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
                             .retain(|&(coords, _)| coords != enemy_coords);
                     }
                 } else if let Some(next_step) =
-                    board.find_enemy_path(&enemy_species, coords)
+                    board.find_enemy_path(&piece.species, coords)
                 {
+                    let species = piece.species;
                     *board.get_mut(next_step).unwrap() =
                         BoardPiece::Combatant(piece);
                     *board.get_mut(coords).unwrap() = BoardPiece::Space;
                     if let Some(enemy_coords) =
-                        board.find_adjacent_enemy(&enemy_species, next_step)
+                        board.find_adjacent_enemy(&species, next_step)
                     {
                         board.attack(next_step, enemy_coords);
                         board
@@ -583,16 +647,18 @@ fn part2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
             }
         }
     }
-    Err(Box::new(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "This should be unreachable code",
-    )))
+    Err(format!(
+        "species {:?} can't win without losses at any attack power",
+        config.boosted.0
+    )
+    .into())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string("day15/input.txt")?;
-    println!("Part 1: {:?}", part1(&input)?);
-    println!("Part 2: {:?}", part2(&input)?);
+    let config = CombatConfig::default();
+    println!("Part 1: {:?}", part1(&input, &config)?);
+    println!("Part 2: {:?}", part2(&input, &config)?);
     Ok(())
 }
 
@@ -685,7 +751,7 @@ mod tests {
                 .filter(|line| !line.trim().is_empty())
                 .collect::<Vec<&str>>()
                 .join("\n");
-            assert_eq!(part1(&board).unwrap(), score);
+            assert_eq!(part1(&board, &CombatConfig::default()).unwrap(), score);
         }
     }
 
@@ -758,11 +824,66 @@ mod tests {
         assert_eq!(
             *board.get((2, 1)).unwrap(),
             BoardPiece::Combatant(Combatant {
-                species: Species::Goblin,
+                species: Species::GOBLIN,
                 hit_points: 200,
                 attack_power: 3,
             })
         );
         assert_eq!(board.get_mut((1, 1)), Some(&mut BoardPiece::Space));
     }
+
+    #[test]
+    fn test_factions() {
+        let input = "#####\n#GED#\n#####";
+        assert!(input.parse::<Board>().is_err());
+
+        // Dwarves hit hard but don't last; the elf is caught between them and the
+        // goblin and goes down first
+        let mut species = CombatConfig::default().species;
+        species.push(SpeciesConfig {
+            species: Species('D'),
+            hit_points: 10,
+            attack_power: 100,
+        });
+        let mut config = CombatConfig::new(species, Species::ELF).unwrap();
+        let board = Board::with_config(input, &config).unwrap();
+        assert_eq!(board.to_string(), input);
+        assert_eq!(part1(input, &config).unwrap(), 4 * 100);
+
+        // Hit points bigger than the old `u8` could hold
+        config.species[0].hit_points = 1000;
+        assert_eq!(part1(input, &config).unwrap(), 4 * 900);
+
+        // Symbols must be distinct from each other and from the map's own
+        let mut species = CombatConfig::default().species;
+        for symbol in ['#', '.', 'G'] {
+            species.push(SpeciesConfig {
+                species: Species(symbol),
+                hit_points: 1,
+                attack_power: 1,
+            });
+            assert!(CombatConfig::new(species.clone(), Species::ELF).is_err());
+            species.pop();
+        }
+        assert!(CombatConfig::new(species, Species('D')).is_err());
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = "#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######";
+        assert_eq!(part2(input, &CombatConfig::default()).unwrap(), 4988);
+
+        // Boosting the goblins instead, which already win at their usual power
+        let config = CombatConfig {
+            boosted: Species::GOBLIN,
+            ..CombatConfig::default()
+        };
+        assert_eq!(part2(input, &config).unwrap(), 27730);
+    }
 }
@@ -16,47 +16,138 @@
 //
 // How many units remain after fully reacting the polymer you scanned? (Note: in this puzzle and others, the input is large; if you copy/paste your input, make sure you get the whole thing.)
 
-fn part1(input: &str) -> usize {
-    let mut index = 0;
-    let mut chars = input.chars().collect::<Vec<_>>();
-    loop {
-        match (chars.get(index), chars.get(index + 1)) {
-            (Some(ch0), Some(ch1))
-                if (ch0 != ch1) && (ch0.to_lowercase().eq(ch1.to_lowercase())) =>
-            {
-                chars.remove(index + 1);
-                chars.remove(index);
-                if index > 0 {
-                    index -= 1;
+use std::str::FromStr;
+
+/// A rule deciding whether two adjacent units, in polymer order, annihilate one another.
+#[derive(Debug, Clone)]
+enum Rule {
+    /// Same letter, opposite case, in either order. Uses Unicode case mapping, so `é` and `É`
+    /// react just like `a` and `A`.
+    OppositeCase,
+    /// The two units destroy each other regardless of which comes first.
+    Pair(char, char),
+    /// Only the first unit followed immediately by the second reacts (`aB` but not `Ba`).
+    Ordered(char, char),
+    /// Any other reaction, given as a predicate over `(left, right)`. Only tests build these
+    /// for now, as there's no way to give one on the command line.
+    #[cfg_attr(not(test), allow(dead_code))]
+    Custom(fn(char, char) -> bool),
+}
+
+impl Rule {
+    fn reacts(&self, left: char, right: char) -> bool {
+        match *self {
+            Rule::OppositeCase => left != right && left.to_lowercase().eq(right.to_lowercase()),
+            Rule::Pair(a, b) => (left, right) == (a, b) || (left, right) == (b, a),
+            Rule::Ordered(a, b) => (left, right) == (a, b),
+            Rule::Custom(f) => f(left, right),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Box<dyn std::error::Error>;
+
+    /// Parses the rules that can be given on the command line, `pair:ab` and `ordered:xY`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, units) = s
+            .split_once(':')
+            .ok_or_else(|| format!("rule {:?} should look like `pair:ab`", s))?;
+        let units = units.chars().collect::<Vec<_>>();
+        let (a, b) = match units[..] {
+            [a, b] => (a, b),
+            _ => return Err(format!("rule {:?} should name exactly two units", s).into()),
+        };
+        match kind {
+            "pair" => Ok(Rule::Pair(a, b)),
+            "ordered" => Ok(Rule::Ordered(a, b)),
+            _ => Err(format!("unknown rule kind {:?} in {:?}", kind, s).into()),
+        }
+    }
+}
+
+/// Reduces strings of units by repeatedly deleting adjacent pairs matched by any of its rules.
+#[derive(Debug, Clone)]
+struct Reactor {
+    rules: Vec<Rule>,
+}
+
+impl Reactor {
+    fn new(rules: Vec<Rule>) -> Self {
+        Reactor { rules }
+    }
+
+    /// The puzzle's polymer: same type, opposite polarity.
+    fn polymer() -> Self {
+        Reactor::new(vec![Rule::OppositeCase])
+    }
+
+    fn reacts(&self, left: char, right: char) -> bool {
+        self.rules.iter().any(|rule| rule.reacts(left, right))
+    }
+
+    /// Fully reacts a polymer in a single pass, returning the reduced polymer.
+    ///
+    /// Each unit is pushed onto a stack; if it reacts with the unit on top, both are destroyed
+    /// instead, which exposes the previous unit to the next one in line.
+    fn react(&self, units: impl IntoIterator<Item = char>) -> String {
+        let mut stack: Vec<char> = Vec::new();
+        for unit in units {
+            match stack.last() {
+                Some(&top) if self.reacts(top, unit) => {
+                    stack.pop();
                 }
+                _ => stack.push(unit),
             }
-            (_, Some(_)) => index += 1,
-            (_, None) => break,
         }
+        stack.into_iter().collect()
+    }
+
+    /// Reacts `polymer` again with every unit of the same type as `unit` (either case) removed.
+    ///
+    /// As long as removed units only ever react with other removed units, as with the puzzle's
+    /// rules, removing them never makes previously reacted pairs reappear, so this can be fed the
+    /// already reacted polymer rather than the raw input.
+    fn react_without(&self, polymer: &str, unit: char) -> String {
+        self.react(
+            polymer
+                .chars()
+                .filter(|c| !c.to_lowercase().eq(unit.to_lowercase())),
+        )
     }
-    chars.iter().collect::<String>().len()
+}
+
+fn part1(input: &str) -> usize {
+    Reactor::polymer().react(input.chars()).chars().count()
 }
 
 fn part2(input: &str) -> usize {
-    (b'a'..b'z' + 1)
-        .map(|c| {
-            part1(
-                input
-                    .replace(c as char, "")
-                    .replace((c as char).to_ascii_uppercase(), "")
-                    .as_str(),
-            )
-        })
+    let reactor = Reactor::polymer();
+    let reacted = reactor.react(input.chars());
+    ('a'..='z')
+        .map(|unit| reactor.react_without(&reacted, unit).chars().count())
         .min()
         .unwrap()
 }
 
-fn main() {
-
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let input = std::fs::read_to_string("day5/input.txt").expect("unable to read input file");
     let input = input.trim();
     println!("part1: {}", part1(input));
     println!("part2: {}", part2(input));
+
+    // Optionally react the polymer with extra rules too, e.g. `day5 pair:ab ordered:xY`
+    let extra = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse())
+        .collect::<Result<Vec<Rule>, _>>()?;
+    if !extra.is_empty() {
+        let mut rules = vec![Rule::OppositeCase];
+        rules.extend(extra);
+        let reacted = Reactor::new(rules).react(input.chars());
+        println!("with extra rules: {}", reacted.chars().count());
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -71,4 +162,50 @@ mod tests {
         assert_eq!(part1("aABBCC"), 4);
         assert_eq!(part1("dabAcCaCBAcCcaDA"), 10);
     }
+
+    #[test]
+    fn test_react() {
+        let reactor = Reactor::polymer();
+        assert_eq!(reactor.react("dabAcCaCBAcCcaDA".chars()), "dabCBAcaDA");
+        assert_eq!(reactor.react("abBA".chars()), "");
+        assert_eq!(reactor.react("aabAAB".chars()), "aabAAB");
+        assert_eq!(reactor.react("xéÉyΣσ".chars()), "xy");
+    }
+
+    #[test]
+    fn test_rules() {
+        let reactor = Reactor::new(vec![Rule::Pair('x', 'y')]);
+        assert_eq!(reactor.react("axyb".chars()), "ab");
+        assert_eq!(reactor.react("ayxb".chars()), "ab");
+        assert_eq!(reactor.react("aXyb".chars()), "aXyb");
+
+        let reactor = Reactor::new(vec![Rule::Ordered('a', 'B')]);
+        assert_eq!(reactor.react("aB".chars()), "");
+        assert_eq!(reactor.react("Ba".chars()), "Ba");
+        assert_eq!(reactor.react("aaBB".chars()), "");
+
+        let reactor = Reactor::new(vec![
+            Rule::Ordered('(', ')'),
+            Rule::Custom(|l, r| l.is_ascii_digit() && l == r),
+        ]);
+        assert_eq!(reactor.react("(1(22)1)x".chars()), "x");
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert!(matches!("pair:ab".parse(), Ok(Rule::Pair('a', 'b'))));
+        assert!(matches!("ordered:éB".parse(), Ok(Rule::Ordered('é', 'B'))));
+        for bad in ["pair", "pair:abc", "pair:a", "swap:ab", ""] {
+            assert!(bad.parse::<Rule>().is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_part2() {
+        let reactor = Reactor::polymer();
+        let reacted = reactor.react("dabAcCaCBAcCcaDA".chars());
+        assert_eq!(reactor.react_without(&reacted, 'a'), "dbCBcD");
+        assert_eq!(reactor.react_without(&reacted, 'c'), "daDA");
+        assert_eq!(part2("dabAcCaCBAcCcaDA"), 4);
+    }
 }
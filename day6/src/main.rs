@@ -1,26 +1,28 @@
 use nom::{complete, do_parse, is_digit, many1, map_res, named, newline, opt, tag, take_while};
+use std::fmt;
 
 #[derive(Debug)]
 struct Point {
-    x: u16,
-    y: u16,
+    x: i32,
+    y: i32,
     nearest: Option<usize>,
 }
 
 struct Grid {
-    min_x: u16,
-    max_x: u16,
-    min_y: u16,
-    max_y: u16,
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
     points: Vec<Point>,
 }
 
 impl Grid {
-    fn new(points: &[Point]) -> Self {
-        let min_x = points.iter().min_by_key(|p| p.x).unwrap().x;
-        let max_x = points.iter().max_by_key(|p| p.x).unwrap().x;
-        let min_y = points.iter().min_by_key(|p| p.y).unwrap().y;
-        let max_y = points.iter().max_by_key(|p| p.y).unwrap().y;
+    /// Builds a grid covering the bounding box of `points`, grown by `margin` on every side.
+    fn new(points: &[Point], margin: i32) -> Self {
+        let min_x = points.iter().min_by_key(|p| p.x).unwrap().x - margin;
+        let max_x = points.iter().max_by_key(|p| p.x).unwrap().x + margin;
+        let min_y = points.iter().min_by_key(|p| p.y).unwrap().y - margin;
+        let max_y = points.iter().max_by_key(|p| p.y).unwrap().y + margin;
 
         Grid {
             min_x,
@@ -38,12 +40,428 @@ impl Grid {
                 .collect::<Vec<_>>(),
         }
     }
+
+    fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y) {
+            Some((x - self.min_x) as usize * self.height() + (y - self.min_y) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// How far apart two cells are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    /// `|dx| + |dy|`, as in the puzzle.
+    Manhattan,
+    /// `max(|dx|, |dy|)`, i.e. king moves on a chessboard.
+    Chebyshev,
+    /// `dx² + dy²`, which ranks cells the same as straight-line distance without needing floats.
+    SquaredEuclidean,
+}
+
+impl Metric {
+    fn distance(self, a: &Point, b: &Point) -> i64 {
+        let dx = (i64::from(a.x) - i64::from(b.x)).abs();
+        let dy = (i64::from(a.y) - i64::from(b.y)).abs();
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::SquaredEuclidean => dx * dx + dy * dy,
+        }
+    }
+
+    /// The moves for which a breadth-first flood measures exactly this metric, if there are any.
+    fn steps(self) -> Option<&'static [(i32, i32)]> {
+        match self {
+            Metric::Manhattan => Some(&[(-1, 0), (1, 0), (0, -1), (0, 1)]),
+            Metric::Chebyshev => Some(&[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ]),
+            Metric::SquaredEuclidean => None,
+        }
+    }
+}
+
+/// Nearest-coordinate ownership for every cell around a set of coordinates. Cells nearest to more
+/// than one coordinate are left with `nearest: None`.
+struct Voronoi {
+    grid: Grid,
+    /// Whether each coordinate's region extends forever.
+    infinite: Vec<bool>,
+}
+
+impl Voronoi {
+    fn new(coords: &[Point], metric: Metric) -> Self {
+        // Two cells of padding is enough to hold the cells `infinite_regions` reads from the grid.
+        let mut grid = Grid::new(coords, 2);
+        match metric.steps() {
+            Some(steps) => Voronoi::flood(&mut grid, coords, steps),
+            None => Voronoi::scan(&mut grid, coords, metric),
+        }
+        let infinite = Voronoi::infinite_regions(&grid, coords, metric);
+        Voronoi { grid, infinite }
+    }
+
+    /// Floods outward from all coordinates at once.
+    ///
+    /// Breadth-first, so every cell at distance `d` is settled before any at `d + 1`. A cell
+    /// reached by different owners in the same round is tied, and ties spread outward since the
+    /// cells behind them are equally near to both owners.
+    fn flood(grid: &mut Grid, coords: &[Point], steps: &[(i32, i32)]) {
+        let mut distances: Vec<Option<u32>> = vec![None; grid.points.len()];
+        let mut queue = std::collections::VecDeque::new();
+
+        for (i, coord) in coords.iter().enumerate() {
+            let idx = grid.index(coord.x, coord.y).unwrap();
+            if distances[idx].is_some() {
+                // Duplicate coordinate: nobody owns this cell
+                grid.points[idx].nearest = None;
+            } else {
+                distances[idx] = Some(0);
+                grid.points[idx].nearest = Some(i);
+                queue.push_back(idx);
+            }
+        }
+
+        while let Some(idx) = queue.pop_front() {
+            let (x, y, owner) = {
+                let p = &grid.points[idx];
+                (p.x, p.y, p.nearest)
+            };
+            let distance = distances[idx].unwrap() + 1;
+            for (dx, dy) in steps {
+                let nidx = match grid.index(x + dx, y + dy) {
+                    Some(nidx) => nidx,
+                    None => continue,
+                };
+                match distances[nidx] {
+                    None => {
+                        distances[nidx] = Some(distance);
+                        grid.points[nidx].nearest = owner;
+                        queue.push_back(nidx);
+                    }
+                    Some(d) if d == distance && grid.points[nidx].nearest != owner => {
+                        grid.points[nidx].nearest = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Compares every cell against every coordinate, for metrics a flood can't follow.
+    fn scan(grid: &mut Grid, coords: &[Point], metric: Metric) {
+        for cell in grid.points.iter_mut() {
+            cell.nearest = Voronoi::nearest(coords, metric, cell);
+        }
+    }
+
+    /// The one coordinate nearest to `cell`, if it isn't tied with another.
+    fn nearest(coords: &[Point], metric: Metric, cell: &Point) -> Option<usize> {
+        let distances = coords
+            .iter()
+            .map(|p| metric.distance(p, cell))
+            .collect::<Vec<_>>();
+        let min = *distances.iter().min()?;
+        let mut nearest = distances.iter().enumerate().filter(|(_i, d)| **d == min);
+        match (nearest.next(), nearest.next()) {
+            (Some((i, _d)), None) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Works out which coordinates own regions that never end.
+    fn infinite_regions(grid: &Grid, coords: &[Point], metric: Metric) -> Vec<bool> {
+        let mut infinite = vec![false; coords.len()];
+        let bounds = |f: &dyn Fn(&Point) -> i32| {
+            (
+                coords.iter().map(f).min().unwrap(),
+                coords.iter().map(f).max().unwrap(),
+            )
+        };
+
+        // Past the extremes of `f` over the coordinates, stepping further out adds the same
+        // amount to the distance to every coordinate, so the owner of any cell out there also
+        // owns every cell beyond it. For Manhattan `f` is `x` or `y` with single steps, and the
+        // nearest such cells all fit within the grid's padding.
+        let beyond: Box<dyn Fn(&Point) -> bool> = match metric {
+            Metric::Manhattan => {
+                let (xs, ys) = (bounds(&|p| p.x), bounds(&|p| p.y));
+                Box::new(move |p| p.x < xs.0 || p.x > xs.1 || p.y < ys.0 || p.y > ys.1)
+            }
+            Metric::Chebyshev => {
+                // For Chebyshev `f` is `u = x + y` or `v = x - y` with diagonal steps, which move
+                // `u` or `v` by 2. A cell beyond both a `u` and a `v` extreme can be walked back
+                // along either diagonal without changing owner, so every infinite region owns a
+                // cell one or two past some extreme of one and at most two past the extremes of
+                // the other. Those cells reach up to half the bounding box's diagonal outside
+                // it, past the grid, so they're checked directly.
+                let (us, vs) = (bounds(&|p| p.x + p.y), bounds(&|p| p.x - p.y));
+                let ring = |near: (i32, i32), far: (i32, i32)| {
+                    [near.0 - 2, near.0 - 1, near.1 + 1, near.1 + 2]
+                        .into_iter()
+                        .flat_map(move |a| (far.0 - 2..=far.1 + 2).map(move |b| (a, b)))
+                };
+                let uv = ring(us, vs).chain(ring(vs, us).map(|(v, u)| (u, v)));
+                for (u, v) in uv.filter(|(u, v)| (u + v) % 2 == 0) {
+                    let cell = Point {
+                        x: (u + v) / 2,
+                        y: (u - v) / 2,
+                        nearest: None,
+                    };
+                    if let Some(i) = Voronoi::nearest(coords, metric, &cell) {
+                        infinite[i] = true;
+                    }
+                }
+                return infinite;
+            }
+            Metric::SquaredEuclidean => {
+                // Straight-line regions are unbounded exactly for coordinates on the boundary of
+                // the convex hull, i.e. those with a line through them and some other coordinate
+                // that leaves every coordinate on one side. Duplicates own nothing at all.
+                for (i, p) in coords.iter().enumerate() {
+                    let duplicated = coords
+                        .iter()
+                        .enumerate()
+                        .any(|(j, q)| i != j && (p.x, p.y) == (q.x, q.y));
+                    let distinct = coords.iter().filter(|q| (p.x, p.y) != (q.x, q.y));
+                    let on_hull = distinct.clone().next().is_none()
+                        || distinct.clone().any(|q| {
+                            let sides = coords.iter().map(|r| {
+                                (i64::from(q.x) - i64::from(p.x))
+                                    * (i64::from(r.y) - i64::from(p.y))
+                                    - (i64::from(q.y) - i64::from(p.y))
+                                        * (i64::from(r.x) - i64::from(p.x))
+                            });
+                            sides.clone().all(|s| s >= 0) || sides.clone().all(|s| s <= 0)
+                        });
+                    infinite[i] = on_hull && !duplicated;
+                }
+                return infinite;
+            }
+        };
+
+        for p in grid.points.iter().filter(|p| beyond(p)) {
+            if let Some(i) = p.nearest {
+                infinite[i] = true;
+            }
+        }
+        infinite
+    }
+
+    /// Number of cells owned by each coordinate. Infinite regions are only counted within the
+    /// grid.
+    fn areas(&self) -> Vec<usize> {
+        let mut areas = vec![0; self.infinite.len()];
+        for i in self.grid.points.iter().filter_map(|p| p.nearest) {
+            areas[i] += 1;
+        }
+        areas
+    }
+
+    /// The index and size of the largest finite region, if any.
+    fn largest_finite(&self) -> Option<(usize, usize)> {
+        self.areas()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _area)| !self.infinite[*i])
+            .max_by_key(|(_i, area)| *area)
+    }
+}
+
+/// Counts the cells whose total distance to every coordinate is less than `threshold`.
+///
+/// Every metric here is at least `|dx|`, so no such cell lies more than `threshold / n` beyond the
+/// coordinates in either axis. Manhattan and squared Euclidean distances also split into
+/// independent `x` and `y` sums, so for those the sums are tabulated once per column and per row
+/// and then paired up; Chebyshev has to check each cell.
+fn safe_region_size(coords: &[Point], metric: Metric, threshold: i64) -> usize {
+    let reach = (threshold / coords.len() as i64 + 1) as i32;
+
+    let axis_term = match metric {
+        Metric::Manhattan => |d: i64| d.abs(),
+        Metric::SquaredEuclidean => |d: i64| d * d,
+        Metric::Chebyshev => {
+            let grid = Grid::new(coords, reach);
+            return grid
+                .points
+                .iter()
+                .filter(|cell| {
+                    coords.iter().map(|p| metric.distance(p, cell)).sum::<i64>() < threshold
+                })
+                .count();
+        }
+    };
+
+    let sums = |axis: &dyn Fn(&Point) -> i32| -> Vec<i64> {
+        let min = coords.iter().map(axis).min().unwrap() - reach;
+        let max = coords.iter().map(axis).max().unwrap() + reach;
+        (min..=max)
+            .map(|v| {
+                coords
+                    .iter()
+                    .map(|p| axis_term(i64::from(axis(p) - v)))
+                    .sum()
+            })
+            .collect()
+    };
+    let x_sums = sums(&|p| p.x);
+    let mut y_sums = sums(&|p| p.y);
+    y_sums.sort_unstable();
+
+    x_sums
+        .iter()
+        .map(|sx| y_sums.partition_point(|sy| sx + sy < threshold))
+        .sum()
+}
+
+/// Draws the regions of a `Voronoi` map.
+///
+/// In the text form the cells each coordinate owns are labelled `a` to `z` and then `A` to `Z`,
+/// or with two of those letters apiece if there are more coordinates than that. Coordinates
+/// themselves are drawn as `@`, tied cells as `.`, the rest of the largest finite region as `+`,
+/// and cells in the safe region, if one is laid over the map, as `#`.
+struct RegionMap<'a> {
+    voronoi: &'a Voronoi,
+    coords: &'a [Point],
+    largest: Option<usize>,
+    /// Whether each grid cell lies in the safe region.
+    safe: Vec<bool>,
+}
+
+impl<'a> RegionMap<'a> {
+    fn new(voronoi: &'a Voronoi, coords: &'a [Point]) -> Self {
+        RegionMap {
+            voronoi,
+            coords,
+            largest: voronoi.largest_finite().map(|(i, _area)| i),
+            safe: vec![false; voronoi.grid.points.len()],
+        }
+    }
+
+    /// Marks the cells whose total distance to every coordinate is less than `threshold`. Only
+    /// the part of the safe region that falls within the map is shown.
+    fn with_safe_region(mut self, metric: Metric, threshold: i64) -> Self {
+        self.safe = self
+            .voronoi
+            .grid
+            .points
+            .iter()
+            .map(|cell| {
+                self.coords
+                    .iter()
+                    .map(|p| metric.distance(p, cell))
+                    .sum::<i64>()
+                    < threshold
+            })
+            .collect();
+        self
+    }
+
+    fn is_coord(&self, cell: &Point) -> bool {
+        self.coords.iter().any(|p| (p.x, p.y) == (cell.x, cell.y))
+    }
+
+    /// Encodes the map as a binary PPM image, one pixel per cell. Regions get evenly spread hues,
+    /// dimmed except for the largest finite region; ties are dark grey, coordinates white, and the
+    /// safe region is washed halfway to white.
+    fn to_ppm(&self) -> Vec<u8> {
+        let grid = &self.voronoi.grid;
+        let width = grid.max_x - grid.min_x + 1;
+        let mut ppm = format!("P6\n{} {}\n255\n", width, grid.height()).into_bytes();
+
+        for y in grid.min_y..=grid.max_y {
+            for x in grid.min_x..=grid.max_x {
+                let idx = grid.index(x, y).unwrap();
+                let cell = &grid.points[idx];
+                let mut rgb = match cell.nearest {
+                    _ if self.is_coord(cell) => [255, 255, 255],
+                    None => [48, 48, 48],
+                    Some(i) if Some(i) == self.largest => hue(i),
+                    Some(i) => hue(i).map(|c| c / 2),
+                };
+                if self.safe[idx] {
+                    rgb = rgb.map(|c| c / 2 + 128);
+                }
+                ppm.extend_from_slice(&rgb);
+            }
+        }
+        ppm
+    }
+}
+
+/// A bright colour for region `i`, stepping round the colour wheel by the golden angle so that
+/// neighbouring indices look nothing alike.
+fn hue(i: usize) -> [u8; 3] {
+    let h = (i as f64 * 0.618_033_988_75).fract() * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u8 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b].map(|c: f64| (c * 255.0).round() as u8)
+}
+
+const LABELS: &[u8; 52] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+impl fmt::Display for RegionMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let grid = &self.voronoi.grid;
+        let width = if self.coords.len() <= LABELS.len() {
+            1
+        } else {
+            2
+        };
+        let label = |i: usize| {
+            let letters = [i / LABELS.len() % LABELS.len(), i % LABELS.len()];
+            letters[2 - width..]
+                .iter()
+                .map(|&l| LABELS[l] as char)
+                .collect::<String>()
+        };
+        let symbol = |c: char| c.to_string().repeat(width);
+        let lines = (grid.min_y..=grid.max_y)
+            .map(|y| {
+                (grid.min_x..=grid.max_x)
+                    .map(|x| {
+                        let idx = grid.index(x, y).unwrap();
+                        let cell = &grid.points[idx];
+                        match cell.nearest {
+                            _ if self.is_coord(cell) => symbol('@'),
+                            _ if self.safe[idx] => symbol('#'),
+                            Some(i) if Some(i) == self.largest => symbol('+'),
+                            Some(i) => label(i),
+                            None => symbol('.'),
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
 }
 
-named!(parse_num<&[u8], u16>,
+named!(parse_num<&[u8], i32>,
        map_res!(
            map_res!(take_while!(is_digit), std::str::from_utf8), {
-           |s: &str| s.parse::<u16>()
+           |s: &str| s.parse::<i32>()
                })
        );
 
@@ -62,96 +480,39 @@ named!(parse_input<&[u8], Vec<Point>>,
 );
 
 fn part1(points: &[Point]) -> u32 {
-    let mut grid = Grid::new(points);
-
-    // For each point in the grid, find the nearest point
-    for point in grid.points.iter_mut() {
-        point.nearest = {
-            // Vec<(id, distance>
-            let distances = points
-                .iter()
-                .enumerate()
-                .map(|(idx, p)| {
-                    (
-                        idx,
-                        (p.x as i32 - point.x as i32).abs() + (p.y as i32 - point.y as i32).abs(),
-                    )
-                })
-                .collect::<Vec<_>>();
-            let min_distance = distances.iter().min_by_key(|(_i, d)| d).unwrap();
-            if distances
-                .iter()
-                .filter(|(_i, d)| *d == min_distance.1)
-                .count()
-                > 1
-            {
-                // grid with multiple nearest points
-                None
-            } else {
-                // index of single nearest point
-                Some(min_distance.0)
-            }
-        }
-    }
-
-    // Collect points into tuples of (index, count of grid with this point as nearest)
-    let touches_edges = grid
-        .points
-        .iter()
-        .filter_map(|p| {
-            if [grid.min_x, grid.max_x].contains(&p.x) || [grid.min_y, grid.max_y].contains(&p.y) {
-                Some(p.nearest)
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
-    let counts = points
-        .iter()
-        .enumerate()
-        .map(|(i, _p)| {
-            (
-                i,
-                grid.points
-                    .iter()
-                    .filter(|&p| {
-                        if let Some(nearest) = p.nearest {
-                            nearest == i
-                        } else {
-                            false
-                        }
-                    })
-                    .count(),
-            )
-        })
-        .collect::<Vec<_>>();
-    counts
-        .iter()
-        .filter(|(i, _c)| !touches_edges.contains(&Some(*i)))
-        .max_by_key(|(_i, c)| c)
+    Voronoi::new(points, Metric::Manhattan)
+        .largest_finite()
         .unwrap()
         .1 as u32
 }
 
-fn part2(points: &[Point]) -> u32 {
-    Grid::new(points)
-        .points
-        .iter()
-        .filter(|&gp| {
-            points
-                .iter()
-                .map(|p| (p.x as i32 - gp.x as i32).abs() + (p.y as i32 - gp.y as i32).abs())
-                .sum::<i32>()
-                < 10000
-        })
-        .count() as u32
+fn part2(points: &[Point], threshold: i64) -> u32 {
+    safe_region_size(points, Metric::Manhattan, threshold) as u32
 }
 
 fn main() {
     let input = include_bytes!("../input.txt");
     let (_incomplete, points) = parse_input(input).unwrap();
     println!("part 1 solution: {}", part1(&points));
-    println!("part 2 solution: {}", part2(&points));
+    println!("part 2 solution: {}", part2(&points, 10000));
+
+    // Optionally save a picture of the regions, e.g. `day6 regions.ppm`, or print them with
+    // `day6 --ascii`, measuring distances another way if asked, e.g. `day6 regions.ppm chebyshev`
+    if let Some(path) = std::env::args().nth(1) {
+        let metric = match std::env::args().nth(2).as_deref() {
+            None | Some("manhattan") => Metric::Manhattan,
+            Some("chebyshev") => Metric::Chebyshev,
+            Some("euclidean") => Metric::SquaredEuclidean,
+            Some(other) => panic!("unknown metric: {}", other),
+        };
+        let voronoi = Voronoi::new(&points, metric);
+        let map = RegionMap::new(&voronoi, &points).with_safe_region(metric, 10000);
+        if path == "--ascii" {
+            println!("{}", map);
+        } else {
+            std::fs::write(path, map.to_ppm()).expect("unable to write region map");
+        }
+    }
 }
 
 #[cfg(test)]
@@ -191,6 +552,244 @@ mod tests {
                 nearest: None,
             },
         ];
-        assert_eq!(part1(&points), 17)
+        assert_eq!(part1(&points), 17);
+        assert_eq!(part2(&points, 32), 16);
+
+        let voronoi = Voronoi::new(&points, Metric::Manhattan);
+        assert_eq!(voronoi.infinite, [true, true, true, false, false, true]);
+        assert_eq!(voronoi.areas()[3], 9);
+        assert_eq!(voronoi.largest_finite(), Some((4, 17)));
+    }
+
+    #[test]
+    fn test_ties() {
+        let points = vec![
+            Point {
+                x: 0,
+                y: 0,
+                nearest: None,
+            },
+            Point {
+                x: 2,
+                y: 0,
+                nearest: None,
+            },
+            Point {
+                x: 2,
+                y: 0,
+                nearest: None,
+            },
+        ];
+        let voronoi = Voronoi::new(&points, Metric::Manhattan);
+        let owner = |x, y| voronoi.grid.points[voronoi.grid.index(x, y).unwrap()].nearest;
+        assert_eq!(owner(0, 0), Some(0));
+        assert_eq!(owner(1, 0), None);
+        assert_eq!(owner(1, 1), None);
+        assert_eq!(owner(2, 0), None);
+        assert_eq!(owner(3, 0), None);
+        assert_eq!(voronoi.infinite, [true, false, false]);
+    }
+
+    #[test]
+    fn test_region_map() {
+        let mut points = example_points();
+        points.truncate(6);
+        let voronoi = Voronoi::new(&points, Metric::Manhattan);
+        let map = RegionMap::new(&voronoi, &points);
+        let expected = [
+            "aaaaaa.ccccc",
+            "aaaaaa.ccccc",
+            "aa@aaa.ccccc",
+            "aaaadd+ccccc",
+            "aaaddd+cc@cc",
+            "...d@d++cccc",
+            "bbb.d+@++ccc",
+            "bb@b.++++...",
+            "bbbb.+++ffff",
+            "bbbb.++fffff",
+            "bbbb.ffff@ff",
+            "bbbb.fffffff",
+            "bbbb.fffffff",
+        ];
+        assert_eq!(map.to_string(), expected.join("\n"));
+
+        let map = map.with_safe_region(Metric::Manhattan, 32);
+        let lines = map.to_string();
+        assert_eq!(lines.matches('#').count(), 16 - 2);
+        assert_eq!(lines.lines().nth(5).unwrap(), "...#@###cccc");
+
+        let ppm = map.to_ppm();
+        assert!(ppm.starts_with(b"P6\n12 13\n255\n"));
+        assert_eq!(ppm.len(), "P6\n12 13\n255\n".len() + 12 * 13 * 3);
+    }
+
+    #[test]
+    fn test_region_labels() {
+        // Coordinates in a column, each owning the row it's on
+        let column = |n: i32| {
+            (0..n)
+                .map(|y| Point {
+                    x: 0,
+                    y,
+                    nearest: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let label_of_row = |map: &RegionMap, y: usize| {
+            let line = map.to_string().lines().nth(y + 2).unwrap().to_string();
+            line[..line.len() / 5].to_string()
+        };
+
+        let points = column(52);
+        let voronoi = Voronoi::new(&points, Metric::Manhattan);
+        let map = RegionMap::new(&voronoi, &points);
+        let labels = (0..52).map(|y| label_of_row(&map, y)).collect::<String>();
+        assert_eq!(labels.as_bytes(), LABELS);
+
+        let points = column(60);
+        let voronoi = Voronoi::new(&points, Metric::Manhattan);
+        let map = RegionMap::new(&voronoi, &points);
+        assert_eq!(label_of_row(&map, 0), "aa");
+        assert_eq!(label_of_row(&map, 51), "aZ");
+        assert_eq!(label_of_row(&map, 52), "ba");
+        assert_eq!(map.to_string().lines().nth(2).unwrap(), "aaaa@@aaaa");
+    }
+
+    fn example_points() -> Vec<Point> {
+        [
+            (1, 1),
+            (1, 6),
+            (8, 3),
+            (3, 4),
+            (5, 5),
+            (8, 9),
+            (6, 14),
+            (13, 7),
+        ]
+        .iter()
+        .map(|&(x, y)| Point {
+            x,
+            y,
+            nearest: None,
+        })
+        .collect()
+    }
+
+    /// Which coordinates own a cell on a ring of cells far outside them.
+    fn far_owners(points: &[Point], metric: Metric) -> Vec<bool> {
+        let (min_x, max_x) = (
+            points.iter().map(|p| p.x).min().unwrap() - 200,
+            points.iter().map(|p| p.x).max().unwrap() + 200,
+        );
+        let (min_y, max_y) = (
+            points.iter().map(|p| p.y).min().unwrap() - 200,
+            points.iter().map(|p| p.y).max().unwrap() + 200,
+        );
+        let rows = [min_y, max_y].map(|y| (min_x..=max_x).map(move |x| (x, y)));
+        let columns = [min_x, max_x].map(|x| (min_y..=max_y).map(move |y| (x, y)));
+        let mut far_owners = vec![false; points.len()];
+        for (x, y) in rows
+            .into_iter()
+            .flatten()
+            .chain(columns.into_iter().flatten())
+        {
+            let cell = Point {
+                x,
+                y,
+                nearest: None,
+            };
+            if let Some(i) = Voronoi::nearest(points, metric, &cell) {
+                far_owners[i] = true;
+            }
+        }
+        far_owners
+    }
+
+    #[test]
+    fn test_infinite_regions() {
+        let point = |(x, y)| Point {
+            x,
+            y,
+            nearest: None,
+        };
+
+        // (6, 7) is the only nearest coordinate to every (x, x + 6), far off the grid's padding
+        let coords = [(6, 7), (4, 1), (0, 7), (10, 3), (11, 6)].map(point);
+        let voronoi = Voronoi::new(&coords, Metric::Chebyshev);
+        assert!(voronoi.infinite[0]);
+        assert_eq!(voronoi.infinite, far_owners(&coords, Metric::Chebyshev));
+
+        // A seeded sweep of small random layouts
+        let mut seed = 0x2545_f491_u32;
+        let mut random = |n: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % n) as i32
+        };
+        for _ in 0..200 {
+            let len = 2 + random(5) as usize;
+            let coords = (0..len)
+                .map(|_| point((random(13), random(13))))
+                .collect::<Vec<_>>();
+            for metric in [Metric::Manhattan, Metric::Chebyshev] {
+                let voronoi = Voronoi::new(&coords, metric);
+                let expected = far_owners(&coords, metric);
+                assert_eq!(voronoi.infinite, expected, "{:?} {:?}", metric, coords);
+            }
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let points = example_points();
+        let a = &points[0];
+        let b = &points[2];
+        assert_eq!(Metric::Manhattan.distance(a, b), 9);
+        assert_eq!(Metric::Chebyshev.distance(a, b), 7);
+        assert_eq!(Metric::SquaredEuclidean.distance(a, b), 53);
+
+        for &metric in &[
+            Metric::Manhattan,
+            Metric::Chebyshev,
+            Metric::SquaredEuclidean,
+        ] {
+            // Flooding agrees with checking every coordinate
+            let voronoi = Voronoi::new(&points, metric);
+            let mut grid = Grid::new(&points, 2);
+            Voronoi::scan(&mut grid, &points, metric);
+            assert!(voronoi
+                .grid
+                .points
+                .iter()
+                .zip(grid.points.iter())
+                .all(|(a, b)| a.nearest == b.nearest));
+
+            // Regions found to be infinite are exactly those still owning cells far away
+            let far_owners = far_owners(&points, metric);
+            assert_eq!(voronoi.infinite, far_owners, "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn test_safe_region_metrics() {
+        let points = example_points();
+        for &metric in &[
+            Metric::Manhattan,
+            Metric::Chebyshev,
+            Metric::SquaredEuclidean,
+        ] {
+            for &threshold in &[10, 100, 1000] {
+                let grid = Grid::new(&points, threshold as i32 / points.len() as i32 + 1);
+                let expected = grid
+                    .points
+                    .iter()
+                    .filter(|cell| {
+                        points.iter().map(|p| metric.distance(p, cell)).sum::<i64>() < threshold
+                    })
+                    .count();
+                assert_eq!(safe_region_size(&points, metric, threshold), expected);
+            }
+        }
     }
 }
@@ -1,128 +1,562 @@
-use std::cell::RefCell;
-use std::collections::BTreeMap;
-use std::rc::Rc;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
+use std::fmt;
 
-type TaskNode = Rc<RefCell<Task>>;
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// The steps and the order they must happen in. Steps are referred to by their index in `names`,
+/// which is kept sorted so that lower indices always have lexically earlier names.
 #[derive(Debug, Clone)]
-struct Task {
-    name: char,
-    depends_on: Vec<Rc<RefCell<Self>>>,
-    status: Status,
+struct Graph {
+    names: Vec<String>,
+    /// Durations given inline as `name(duration)`, if any.
+    annotated: Vec<Option<u32>>,
+    /// For each step, the steps that must be finished before it can begin.
+    prerequisites: Vec<Vec<usize>>,
+    /// For each step, the steps waiting on it.
+    dependents: Vec<Vec<usize>>,
 }
 
-impl Task {
-    fn new(name: char) -> Self {
-        Task {
-            name,
-            depends_on: Vec::new(),
-            status: Status::Pending,
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    OnPath,
+    Finished,
+}
+
+impl Graph {
+    /// Builds a graph from `(before, after)` pairs.
+    fn new(edges: &[(&str, &str)]) -> Self {
+        let names = edges
+            .iter()
+            .flat_map(|&(before, after)| vec![before, after])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let index = |name: &str| {
+            names.binary_search_by(|n| n.as_str().cmp(name)).unwrap()
+        };
+
+        let mut prerequisites = vec![Vec::new(); names.len()];
+        let mut dependents = vec![Vec::new(); names.len()];
+        for &(before, after) in edges {
+            prerequisites[index(after)].push(index(before));
+            dependents[index(before)].push(index(after));
+        }
+
+        Graph {
+            annotated: vec![None; names.len()],
+            names,
+            prerequisites,
+            dependents,
         }
     }
-    fn new_node(name: char) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self::new(name)))
+
+    fn len(&self) -> usize {
+        self.names.len()
     }
-}
 
-#[derive(Debug, Clone)]
-enum Status {
-    Pending,
-    InProcess,
-    Done,
+    fn names(&self, steps: &[usize], sep: &str) -> String {
+        steps
+            .iter()
+            .map(|&i| self.names[i].as_str())
+            .collect::<Vec<_>>()
+            .join(sep)
+    }
+
+    /// How long each step takes. A duration in `table` wins over one annotated in the input;
+    /// failing both, a single letter takes `delay` plus its place in the alphabet.
+    fn durations(
+        &self,
+        delay: u32,
+        table: &HashMap<String, u32>,
+    ) -> Result<Vec<u32>> {
+        self.names
+            .iter()
+            .zip(self.annotated.iter())
+            .map(|(name, annotated)| {
+                table
+                    .get(name)
+                    .copied()
+                    .or(*annotated)
+                    .or_else(|| name_to_delay(name).map(|d| delay + d))
+                    .ok_or_else(|| format!("no duration for step {}", name))
+            })
+            .map(|duration| match duration? {
+                0 => Err("durations must be at least one second".into()),
+                d => Ok(d),
+            })
+            .collect()
+    }
+
+    /// Steps in the order they can be done, always taking the lexically first step that's ready.
+    /// Steps caught up in a cycle never become ready and are left out.
+    fn lexical_order(&self) -> Vec<usize> {
+        let mut waiting_on = self
+            .prerequisites
+            .iter()
+            .map(|p| p.len())
+            .collect::<Vec<_>>();
+        let mut ready = (0..self.len())
+            .filter(|&i| waiting_on[i] == 0)
+            .map(Reverse)
+            .collect::<BinaryHeap<_>>();
+
+        let mut order = Vec::with_capacity(self.len());
+        while let Some(Reverse(step)) = ready.pop() {
+            order.push(step);
+            for &next in &self.dependents[step] {
+                waiting_on[next] -= 1;
+                if waiting_on[next] == 0 {
+                    ready.push(Reverse(next));
+                }
+            }
+        }
+        order
+    }
+
+    /// Like `lexical_order`, but fails if any step can never be reached.
+    fn topological_order(&self) -> Result<Vec<usize>> {
+        let order = self.lexical_order();
+        if order.len() == self.len() {
+            return Ok(order);
+        }
+        let cycle = self
+            .find_cycle()
+            .expect("unreachable steps without a cycle");
+        Err(format!(
+            "steps {} form a cycle, so {} can never begin",
+            self.names(&cycle, " -> "),
+            self.names(&self.unreachable(), ", "),
+        )
+        .into())
+    }
+
+    /// Returns the members of some dependency cycle, in order, if there is one.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        let mut visits = vec![Visit::New; self.len()];
+        for start in 0..self.len() {
+            if visits[start] != Visit::New {
+                continue;
+            }
+
+            // Depth-first with an explicit stack of (step, next dependent to look at)
+            visits[start] = Visit::OnPath;
+            let mut path = vec![(start, 0)];
+            while let Some(&(step, child)) = path.last() {
+                let next = match self.dependents[step].get(child) {
+                    Some(&next) => next,
+                    None => {
+                        visits[step] = Visit::Finished;
+                        path.pop();
+                        continue;
+                    }
+                };
+                path.last_mut().unwrap().1 += 1;
+                match visits[next] {
+                    Visit::New => {
+                        visits[next] = Visit::OnPath;
+                        path.push((next, 0));
+                    }
+                    Visit::OnPath => {
+                        let from =
+                            path.iter().position(|&(s, _)| s == next).unwrap();
+                        return Some(
+                            path[from..].iter().map(|&(s, _)| s).collect(),
+                        );
+                    }
+                    Visit::Finished => (),
+                }
+            }
+        }
+        None
+    }
+
+    /// Steps that are referenced but can never begin, because they're in a cycle or wait on one.
+    fn unreachable(&self) -> Vec<usize> {
+        let mut reached = vec![false; self.len()];
+        for step in self.lexical_order() {
+            reached[step] = true;
+        }
+        (0..self.len()).filter(|&i| !reached[i]).collect()
+    }
 }
 
-#[derive(Debug, Clone)]
-enum Worker {
-    Available,
-    WorkingOn((TaskNode, u32)),
+/// Splits a step like `link(30)` into its name and annotated duration.
+fn parse_step(word: &str) -> Result<(&str, Option<u32>)> {
+    match word.strip_suffix(')').and_then(|w| w.split_once('(')) {
+        Some((name, duration)) => Ok((name, Some(duration.parse()?))),
+        None => Ok((word, None)),
+    }
 }
 
-fn parse_input(input: &str) -> BTreeMap<char, TaskNode> {
-    let input = input.to_uppercase();
-    let mut tasks = BTreeMap::new();
+fn parse_input(input: &str) -> Result<Graph> {
+    let mut edges = Vec::new();
+    let mut annotated = BTreeMap::new();
     for line in input.lines() {
         let mut words = line.split_whitespace();
         if let (Some(depends), Some(name)) = (words.nth(1), words.nth(5)) {
-            let depends =
-                depends.parse::<char>().expect("unable to parse as char");
-            let depends = Rc::clone(
-                tasks
-                    .entry(depends)
-                    .or_insert_with(|| Task::new_node(depends)),
-            );
-
-            let name = name.parse::<char>().expect("unable to parse as char");
-            let task =
-                tasks.entry(name).or_insert_with(|| Task::new_node(name));
-            task.borrow_mut().depends_on.push(depends);
+            let (depends, depends_duration) = parse_step(depends)?;
+            let (name, name_duration) = parse_step(name)?;
+            for (step, duration) in
+                [(depends, depends_duration), (name, name_duration)]
+            {
+                if let Some(duration) = duration {
+                    match annotated.insert(step, duration) {
+                        Some(other) if other != duration => {
+                            return Err(format!(
+                                "step {} given durations {} and {}",
+                                step, other, duration
+                            )
+                            .into())
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            edges.push((depends, name));
         }
     }
-    tasks
+
+    let mut graph = Graph::new(&edges);
+    for (annotation, name) in graph.annotated.iter_mut().zip(&graph.names) {
+        *annotation = annotated.get(name.as_str()).copied();
+    }
+    Ok(graph)
 }
 
-fn next_task(btm: &BTreeMap<char, TaskNode>) -> Option<(&char, &TaskNode)> {
-    btm.iter().find(|(_k, v)| {
-        let v = v.borrow();
-        matches!(v.status, Status::Pending)
-            && v.depends_on
-                .iter()
-                .all(|t| matches!(t.borrow().status, Status::Done))
-    })
+fn part1(input: &str) -> Result<String> {
+    let graph = parse_input(input)?;
+    Ok(graph.names(&graph.topological_order()?, ""))
 }
 
-fn part1(input: &str) -> String {
-    let btm = parse_input(input);
-    let mut output = String::new();
-    while let Some((name, task)) = next_task(&btm) {
-        task.borrow_mut().status = Status::Done;
-        output.push(*name);
+/// The puzzle's durations: a single letter takes as many seconds as its place in the alphabet.
+fn name_to_delay(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            Some(u32::from(c.to_ascii_uppercase() as u8 - b'A' + 1))
+        }
+        _ => None,
     }
-    output
 }
 
-fn name_to_delay(c: char) -> u8 {
-    c as u8 - 64
+fn part2(input: &str, num_workers: u32, delay: u32) -> Result<u32> {
+    let graph = parse_input(input)?;
+    let durations = graph.durations(delay, &HashMap::new())?;
+    Ok(simulate(&graph, &durations, num_workers)?.finish)
 }
 
-fn part2(input: &str, num_workers: u32, delay: u32) -> u32 {
-    let btm = parse_input(input);
-    let mut workers = vec![Worker::Available; num_workers as usize];
+/// What a worker was doing over `start..end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Activity {
+    Task(usize),
+    Idle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Interval {
+    activity: Activity,
+    start: u32,
+    end: u32,
+}
+
+/// Who did what, and when, over a simulated run.
+#[derive(Debug, Clone)]
+struct Schedule {
+    /// For each worker, back-to-back intervals covering every second until `finish`.
+    timelines: Vec<Vec<Interval>>,
+    finish: u32,
+}
+
+impl Schedule {
+    fn activity(&self, worker: usize, second: u32) -> Activity {
+        self.timelines[worker]
+            .iter()
+            .find(|i| i.start <= second && second < i.end)
+            .map_or(Activity::Idle, |i| i.activity)
+    }
+
+    /// Every task along with the second it was done, in the order they finished.
+    fn completed(&self) -> Vec<(usize, u32)> {
+        let mut done = self
+            .timelines
+            .iter()
+            .flatten()
+            .filter_map(|i| match i.activity {
+                Activity::Task(task) => Some((task, i.end)),
+                Activity::Idle => None,
+            })
+            .collect::<Vec<_>>();
+        done.sort_by_key(|&(_task, end)| end);
+        done
+    }
+}
+
+/// Works through the steps with `num_workers` workers, always handing the lexically first ready
+/// step to the lowest numbered free worker. Time jumps from one completion to the next, so the
+/// cost depends on the number of steps rather than how long they take.
+fn simulate(
+    graph: &Graph,
+    durations: &[u32],
+    num_workers: u32,
+) -> Result<Schedule> {
+    graph.topological_order()?;
+
+    let mut waiting_on = graph
+        .prerequisites
+        .iter()
+        .map(|p| p.len())
+        .collect::<Vec<_>>();
+    let mut ready = (0..graph.len())
+        .filter(|&i| waiting_on[i] == 0)
+        .collect::<BTreeSet<_>>();
+    let mut free = (0..num_workers as usize).collect::<BTreeSet<_>>();
+    // (done at, worker, task) for everything in progress, soonest first
+    let mut running = BinaryHeap::new();
+    let mut timelines = vec![Vec::new(); num_workers as usize];
     let mut second = 0;
     loop {
-        // First iterate through to complete all tasks that have finished
-        for worker in workers.iter_mut() {
-            if let Worker::WorkingOn((task, done_at)) = worker {
-                if *done_at == second {
-                    task.borrow_mut().status = Status::Done;
-                    *worker = Worker::Available;
+        // Start work on any now unblocked tasks
+        while !free.is_empty() && !ready.is_empty() {
+            let worker = free.pop_first().unwrap();
+            let task = ready.pop_first().unwrap();
+            let done_at = second + durations[task];
+            running.push(Reverse((done_at, worker, task)));
+            record(
+                &mut timelines[worker],
+                Activity::Task(task),
+                second,
+                done_at,
+            );
+        }
+
+        // Nothing changes until the next task is done, so skip straight there
+        second = match running.peek() {
+            Some(&Reverse((done_at, _, _))) => done_at,
+            None => {
+                for timeline in timelines.iter_mut() {
+                    record(timeline, Activity::Idle, second, second);
                 }
+                return Ok(Schedule {
+                    timelines,
+                    finish: second,
+                });
             }
-        }
+        };
 
-        // Start work on any now ublocked tasks
-        for worker in workers.iter_mut() {
-            if let Worker::Available = worker {
-                if let Some((name, task)) = next_task(&btm) {
-                    task.borrow_mut().status = Status::InProcess;
-                    let delay = delay + u32::from(name_to_delay(*name));
-                    *worker =
-                        Worker::WorkingOn((task.clone(), second + delay));
+        while let Some(&Reverse((done_at, worker, task))) = running.peek() {
+            if done_at != second {
+                break;
+            }
+            running.pop();
+            free.insert(worker);
+            for &next in &graph.dependents[task] {
+                waiting_on[next] -= 1;
+                if waiting_on[next] == 0 {
+                    ready.insert(next);
                 }
             }
         }
+    }
+}
 
-        if workers.iter().all(|w| matches!(w, Worker::Available)) {
-            return second;
+/// Appends `activity` over `start..end` to a worker's timeline, filling any gap before it with
+/// idle time.
+fn record(
+    timeline: &mut Vec<Interval>,
+    activity: Activity,
+    start: u32,
+    end: u32,
+) {
+    let free_since = timeline.last().map_or(0, |i| i.end);
+    if free_since < start {
+        timeline.push(Interval {
+            activity: Activity::Idle,
+            start: free_since,
+            end: start,
+        });
+    }
+    if start < end {
+        timeline.push(Interval {
+            activity,
+            start,
+            end,
+        });
+    }
+}
+
+/// When each step could run given as many workers as it takes.
+#[derive(Debug, Clone)]
+struct Timings {
+    earliest_start: Vec<u32>,
+    latest_start: Vec<u32>,
+    /// The shortest possible time for the whole job.
+    length: u32,
+}
+
+impl Timings {
+    fn new(graph: &Graph, durations: &[u32]) -> Result<Self> {
+        let order = graph.topological_order()?;
+
+        let mut earliest_start = vec![0; graph.len()];
+        for &step in &order {
+            earliest_start[step] = graph.prerequisites[step]
+                .iter()
+                .map(|&p| earliest_start[p] + durations[p])
+                .max()
+                .unwrap_or(0);
+        }
+        let length = (0..graph.len())
+            .map(|step| earliest_start[step] + durations[step])
+            .max()
+            .unwrap_or(0);
+
+        let mut latest_start = vec![0; graph.len()];
+        for &step in order.iter().rev() {
+            let latest_finish = graph.dependents[step]
+                .iter()
+                .map(|&d| latest_start[d])
+                .min()
+                .unwrap_or(length);
+            latest_start[step] = latest_finish - durations[step];
+        }
+
+        Ok(Timings {
+            earliest_start,
+            latest_start,
+            length,
+        })
+    }
+
+    /// How long a step can be put off without delaying the whole job.
+    fn slack(&self, step: usize) -> u32 {
+        self.latest_start[step] - self.earliest_start[step]
+    }
+
+    /// A longest chain of steps, each starting the moment the one before it finishes. Where there
+    /// is more than one, takes the lexically first step at each point.
+    fn critical_path(&self, graph: &Graph, durations: &[u32]) -> Vec<usize> {
+        let critical = |step: &usize| self.slack(*step) == 0;
+        let mut path = Vec::new();
+        let mut next = (0..graph.len())
+            .filter(critical)
+            .find(|&step| self.earliest_start[step] == 0);
+        while let Some(step) = next {
+            path.push(step);
+            let finish = self.earliest_start[step] + durations[step];
+            next = graph.dependents[step]
+                .iter()
+                .copied()
+                .filter(critical)
+                .filter(|&d| self.earliest_start[d] == finish)
+                .min();
+        }
+        path
+    }
+}
+
+/// The fewest workers that finish as soon as the critical path allows. Adding workers can
+/// occasionally slow the greedy assignment down, so every count is tried in turn.
+fn min_workers(graph: &Graph, durations: &[u32]) -> Result<u32> {
+    let length = Timings::new(graph, durations)?.length;
+    for num_workers in 1..graph.len() as u32 {
+        if simulate(graph, durations, num_workers)?.finish == length {
+            return Ok(num_workers);
+        }
+    }
+    Ok(graph.len().max(1) as u32)
+}
+
+/// A Gantt chart of a simulated run in the style of the puzzle's example, followed by the critical
+/// path, each step's slack, and how many workers could match the critical path.
+struct Report<'a> {
+    graph: &'a Graph,
+    durations: &'a [u32],
+    schedule: Schedule,
+    timings: Timings,
+    min_workers: u32,
+}
+
+impl<'a> Report<'a> {
+    fn new(
+        graph: &'a Graph,
+        durations: &'a [u32],
+        num_workers: u32,
+    ) -> Result<Self> {
+        Ok(Report {
+            graph,
+            durations,
+            schedule: simulate(graph, durations, num_workers)?,
+            timings: Timings::new(graph, durations)?,
+            min_workers: min_workers(graph, durations)?,
+        })
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let graph = self.graph;
+        let sep = if graph.names.iter().all(|n| n.chars().count() == 1) {
+            ""
         } else {
-            second += 1;
+            ","
+        };
+        let width = graph.names.iter().map(|n| n.len()).max().unwrap_or(0);
+        let width = width.max("Worker 1".len());
+
+        write!(f, "Second")?;
+        for worker in 1..=self.schedule.timelines.len() {
+            write!(f, "   {:^width$}", format!("Worker {}", worker))?;
         }
+        writeln!(f, "   Done")?;
+
+        let completed = self.schedule.completed();
+        for second in 0..=self.schedule.finish {
+            let mut line = format!("{:>4}  ", second);
+            for worker in 0..self.schedule.timelines.len() {
+                let name = match self.schedule.activity(worker, second) {
+                    Activity::Task(task) => graph.names[task].as_str(),
+                    Activity::Idle => ".",
+                };
+                line.push_str(&format!("   {:^width$}", name));
+            }
+            let done = completed
+                .iter()
+                .take_while(|&&(_task, end)| end <= second)
+                .map(|&(task, _end)| task)
+                .collect::<Vec<_>>();
+            line.push_str(&format!("   {}", graph.names(&done, sep)));
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        let path = self.timings.critical_path(graph, self.durations);
+        writeln!(
+            f,
+            "\nCritical path: {} ({} seconds)",
+            graph.names(&path, " -> "),
+            self.timings.length
+        )?;
+        writeln!(f, "Slack:")?;
+        for (step, name) in graph.names.iter().enumerate() {
+            writeln!(f, "  {:width$}  {}", name, self.timings.slack(step))?;
+        }
+        write!(f, "Minimum workers: {}", self.min_workers)
     }
 }
 
-fn main() -> std::io::Result<()> {
+fn main() -> Result<()> {
     let input = std::fs::read_to_string("day7/input.txt")?;
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input, 5, 60));
+    println!("Part 1: {}", part1(&input)?);
+    println!("Part 2: {}", part2(&input, 5, 60)?);
+
+    // Pass `--gantt` to see how the work was shared out
+    if std::env::args().any(|arg| arg == "--gantt") {
+        let graph = parse_input(&input)?;
+        let durations = graph.durations(60, &HashMap::new())?;
+        println!("\n{}", Report::new(&graph, &durations, 5)?);
+    }
     Ok(())
 }
 
@@ -141,7 +575,7 @@ Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin.
 ";
-        assert_eq!(part1(test_input), "CABDFE");
+        assert_eq!(part1(test_input).unwrap(), "CABDFE");
     }
 
     #[test]
@@ -155,12 +589,262 @@ Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin.
 ";
-        assert_eq!(part2(test_input, 2, 0), 15);
+        assert_eq!(part2(test_input, 2, 0).unwrap(), 15);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let test_input = "
+Step C must be finished before step A can begin.
+Step A must be finished before step B can begin.
+Step B must be finished before step D can begin.
+Step D must be finished before step A can begin.
+Step D must be finished before step E can begin.
+";
+        let graph = parse_input(test_input).unwrap();
+        assert_eq!(graph.names(&graph.lexical_order(), ""), "C");
+        assert_eq!(graph.names(&graph.find_cycle().unwrap(), ""), "ABD");
+        assert_eq!(graph.names(&graph.unreachable(), ""), "ABDE");
+
+        let err = part1(test_input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "steps A -> B -> D form a cycle, so A, B, D, E can never begin"
+        );
+        assert!(part2(test_input, 2, 0).is_err());
+
+        let graph =
+            parse_input("Step A must be finished before step A can begin.")
+                .unwrap();
+        assert_eq!(graph.names(&graph.find_cycle().unwrap(), ""), "A");
+        assert_eq!(parse_input("").unwrap().find_cycle(), None);
     }
 
     #[test]
     fn test_name_to_delay() {
-        assert_eq!(name_to_delay('A'), 1);
-        assert_eq!(name_to_delay('Z'), 26);
+        assert_eq!(name_to_delay("A"), Some(1));
+        assert_eq!(name_to_delay("Z"), Some(26));
+        assert_eq!(name_to_delay("z"), Some(26));
+        assert_eq!(name_to_delay("AB"), None);
+        assert_eq!(name_to_delay("@"), None);
+    }
+
+    #[test]
+    fn test_named_steps() {
+        let test_input = "
+Step fetch(3) must be finished before step compile(10) can begin.
+Step fetch must be finished before step docs can begin.
+Step compile must be finished before step link can begin.
+Step docs must be finished before step package(2) can begin.
+Step link must be finished before step package can begin.
+";
+        assert_eq!(part1(test_input).unwrap(), "fetchcompiledocslinkpackage");
+
+        let graph = parse_input(test_input).unwrap();
+        assert_eq!(
+            graph.names(&graph.topological_order().unwrap(), " "),
+            "fetch compile docs link package"
+        );
+        assert!(graph.durations(0, &HashMap::new()).is_err());
+
+        let table = [("docs", 4), ("link", 5), ("fetch", 1)]
+            .iter()
+            .map(|&(name, d)| (name.to_string(), d))
+            .collect::<HashMap<_, _>>();
+        let durations = graph.durations(0, &table).unwrap();
+        assert_eq!(durations, [10, 4, 1, 5, 2]);
+        assert_eq!(simulate(&graph, &durations, 2).unwrap().finish, 18);
+        assert_eq!(simulate(&graph, &durations, 1).unwrap().finish, 22);
+
+        let table = [("docs".to_string(), 0), ("link".to_string(), 5)]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        assert!(graph.durations(0, &table).is_err());
+
+        assert!(parse_input(
+            "Step a(1) must be finished before step a(2) can begin."
+        )
+        .is_err());
+        assert!(parse_input(
+            "Step a(x) must be finished before step b can begin."
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let test_input = "
+Step C must be finished before step A can begin.
+Step C must be finished before step F can begin.
+Step A must be finished before step B can begin.
+Step A must be finished before step D can begin.
+Step B must be finished before step E can begin.
+Step D must be finished before step E can begin.
+Step F must be finished before step E can begin.
+";
+        let graph = parse_input(test_input).unwrap();
+        let durations = graph.durations(0, &HashMap::new()).unwrap();
+
+        let schedule = simulate(&graph, &durations, 2).unwrap();
+        let idx = |name: &str| graph.names.iter().position(|n| n == name);
+        assert_eq!(
+            schedule.timelines[1],
+            [
+                Interval {
+                    activity: Activity::Idle,
+                    start: 0,
+                    end: 3
+                },
+                Interval {
+                    activity: Activity::Task(idx("F").unwrap()),
+                    start: 3,
+                    end: 9
+                },
+                Interval {
+                    activity: Activity::Idle,
+                    start: 9,
+                    end: 15
+                },
+            ]
+        );
+
+        let timings = Timings::new(&graph, &durations).unwrap();
+        assert_eq!(timings.length, 14);
+        assert_eq!(
+            graph.names(&timings.critical_path(&graph, &durations), ""),
+            "CFE"
+        );
+        let slack = (0..graph.len())
+            .map(|step| timings.slack(step))
+            .collect::<Vec<_>>();
+        // A B C D E F
+        assert_eq!(slack, [1, 3, 0, 1, 0, 0]);
+        assert_eq!(min_workers(&graph, &durations).unwrap(), 3);
+
+        let report = Report::new(&graph, &durations, 2).unwrap().to_string();
+        let expected = "\
+Second   Worker 1   Worker 2   Done
+   0        C          .
+   1        C          .
+   2        C          .
+   3        A          F       C
+   4        B          F       CA
+   5        B          F       CA
+   6        D          F       CAB
+   7        D          F       CAB
+   8        D          F       CAB
+   9        D          .       CABF
+  10        E          .       CABFD
+  11        E          .       CABFD
+  12        E          .       CABFD
+  13        E          .       CABFD
+  14        E          .       CABFD
+  15        .          .       CABFDE
+
+Critical path: C -> F -> E (14 seconds)
+Slack:
+  A         1
+  B         3
+  C         0
+  D         1
+  E         0
+  F         0
+Minimum workers: 3";
+        assert_eq!(report, expected);
+    }
+
+    /// The original second-by-second simulation, returning when each task started and when
+    /// everything was done.
+    fn tick(
+        graph: &Graph,
+        durations: &[u32],
+        workers: usize,
+    ) -> (Vec<u32>, u32) {
+        let mut waiting_on = graph
+            .prerequisites
+            .iter()
+            .map(|p| p.len())
+            .collect::<Vec<_>>();
+        let mut ready = (0..graph.len())
+            .filter(|&i| waiting_on[i] == 0)
+            .collect::<BTreeSet<_>>();
+        let mut started = vec![0; graph.len()];
+        let mut working: Vec<Option<(usize, u32)>> = vec![None; workers];
+        let mut second = 0;
+        loop {
+            for worker in working.iter_mut() {
+                if let Some((task, done_at)) = *worker {
+                    if done_at == second {
+                        for &next in &graph.dependents[task] {
+                            waiting_on[next] -= 1;
+                            if waiting_on[next] == 0 {
+                                ready.insert(next);
+                            }
+                        }
+                        *worker = None;
+                    }
+                }
+            }
+            for worker in working.iter_mut().filter(|w| w.is_none()) {
+                if let Some(task) = ready.pop_first() {
+                    started[task] = second;
+                    *worker = Some((task, second + durations[task]));
+                }
+            }
+            if working.iter().all(Option::is_none) {
+                return (started, second);
+            }
+            second += 1;
+        }
+    }
+
+    #[test]
+    fn test_simulate_matches_ticking() {
+        let mut seed = 7_u64;
+        let mut random = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+        for _ in 0..20 {
+            let names =
+                (0..30).map(|i| format!("s{:02}", i)).collect::<Vec<_>>();
+            let mut edges = Vec::new();
+            for after in 1..names.len() {
+                for _ in 0..random(3) {
+                    let before = random(after as u64) as usize;
+                    edges.push((names[before].as_str(), names[after].as_str()));
+                }
+            }
+            let graph = Graph::new(&edges);
+            let durations = (0..graph.len())
+                .map(|_| random(20) as u32 + 1)
+                .collect::<Vec<_>>();
+            for workers in 1..5 {
+                let schedule = simulate(&graph, &durations, workers).unwrap();
+                let (started, finish) =
+                    tick(&graph, &durations, workers as usize);
+                assert_eq!(schedule.finish, finish);
+                for timeline in &schedule.timelines {
+                    for interval in timeline {
+                        if let Activity::Task(task) = interval.activity {
+                            assert_eq!(interval.start, started[task]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_simulate_long_tasks() {
+        let names = (0..5000).map(|i| format!("t{:04}", i)).collect::<Vec<_>>();
+        let edges = names
+            .windows(2)
+            .map(|w| (w[0].as_str(), w[1].as_str()))
+            .collect::<Vec<_>>();
+        let graph = Graph::new(&edges);
+        let durations = vec![500_000; graph.len()];
+        let schedule = simulate(&graph, &durations, 3).unwrap();
+        assert_eq!(schedule.finish, 2_500_000_000);
     }
 }
@@ -1,71 +1,341 @@
-use nom::{alt, count, digit, do_parse, eof, eol, map_res, named, space, types::CompleteStr};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 #[derive(Debug)]
 struct Node {
-    header: (usize, usize),
     children: Vec<Node>,
     metadata: Vec<u32>,
 }
 
-named!(parse_input<CompleteStr, Node>,
-       do_parse!(
-           num_children: map_res!(digit, |CompleteStr(n)| n.parse()) >>
-           space >>
-           num_metadata: map_res!(digit, |CompleteStr(n)| n.parse()) >>
-           space >>
-           children: count!(parse_input, num_children) >>
-           metadata: count!(do_parse!(
-                   num: map_res!(digit, |CompleteStr(n)| n.parse()) >>
-                   alt!(space | eof!() | eol) >>
-                   ( num )
-           ), num_metadata) >>
-           ( Node { header: (num_children, num_metadata), children, metadata } )
-       )
-);
-
-fn metadata_sum(node: &Node) -> u32 {
-    node.metadata.iter().sum::<u32>() + node.children.iter().map(|c| metadata_sum(c)).sum::<u32>()
-}
-
-fn part1(input: &str) -> u32 {
-    let node = match parse_input(CompleteStr(input)) {
-        Ok((_incomplete, node)) => node,
-        Err(e) => panic!("{}", e.to_string()),
+impl Node {
+    fn new(children: Vec<Node>, metadata: Vec<u32>) -> Self {
+        Node { children, metadata }
+    }
+
+    /// A tree `depth` nodes deep (at least one) with one child per node, for trying out very deep
+    /// input. Every node has metadata `[1]` apart from the deepest, which has `[2]`.
+    fn chain(depth: u32) -> Self {
+        let mut tree = Node::new(vec![], vec![2]);
+        for _ in 1..depth.max(1) {
+            tree = Node::new(vec![tree], vec![1]);
+        }
+        tree
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // The default drop recurses through every level of children, which would overflow the
+        // stack on a deep enough tree, so flatten them out first.
+        let mut nodes = std::mem::take(&mut self.children);
+        while let Some(mut node) = nodes.pop() {
+            nodes.append(&mut node.children);
+        }
+    }
+}
+
+/// A node whose header has been read but whose children aren't all there yet.
+struct Partial {
+    header: (usize, usize),
+    children: Vec<Node>,
+}
+
+/// Parses a license file into its root node, using an explicit stack rather than recursion so
+/// that trees of any depth can be read. Fails unless the numbers make up exactly one tree.
+fn parse_input(input: &str) -> Result<Node> {
+    let mut numbers = input.split_whitespace().map(|n| {
+        n.parse::<u32>()
+            .map_err(|e| format!("invalid number {:?}: {}", n, e))
+    });
+    let mut next = |wanted: &str| -> Result<u32> {
+        match numbers.next() {
+            Some(n) => Ok(n?),
+            None => Err(format!("input ended while reading {}", wanted).into()),
+        }
     };
-    metadata_sum(&node)
+
+    let mut stack: Vec<Partial> = Vec::new();
+    loop {
+        let num_children = next("a header")? as usize;
+        let num_metadata = next("a header")? as usize;
+        stack.push(Partial {
+            header: (num_children, num_metadata),
+            children: Vec::with_capacity(num_children.min(1024)),
+        });
+
+        // Close off every node that has all of its children, handing each to its parent
+        while let Some(partial) = stack.last() {
+            if partial.children.len() < partial.header.0 {
+                break;
+            }
+            let partial = stack.pop().unwrap();
+            let metadata = (0..partial.header.1)
+                .map(|_| next("metadata"))
+                .collect::<Result<Vec<_>>>()?;
+            let node = Node {
+                children: partial.children,
+                metadata,
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => {
+                    return match numbers.next() {
+                        None => Ok(node),
+                        Some(_) => Err("trailing data after the root node".into()),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes a node back out in the space-separated license format, so tests can round-trip trees.
+/// Writes a tree back out in the puzzle's input format.
+fn serialize(root: &Node) -> String {
+    enum Part<'a> {
+        Header(&'a Node),
+        Metadata(&'a Node),
+    }
+
+    let mut numbers = Vec::new();
+    let mut stack = vec![Part::Header(root)];
+    while let Some(part) = stack.pop() {
+        match part {
+            Part::Header(node) => {
+                numbers.push(node.children.len().to_string());
+                numbers.push(node.metadata.len().to_string());
+                stack.push(Part::Metadata(node));
+                stack.extend(node.children.iter().rev().map(Part::Header));
+            }
+            Part::Metadata(node) => {
+                numbers.extend(node.metadata.iter().map(|m| m.to_string()));
+            }
+        }
+    }
+    numbers.join(" ")
+}
+
+fn metadata_sum(root: &Node) -> u32 {
+    let mut stack = vec![root];
+    let mut sum = 0;
+    while let Some(node) = stack.pop() {
+        sum += node.metadata.iter().sum::<u32>();
+        stack.extend(node.children.iter());
+    }
+    sum
+}
+
+fn part1(input: &str) -> Result<u32> {
+    Ok(metadata_sum(&parse_input(input)?))
 }
 
-fn root_sum(node: &Node) -> u32 {
+/// A node's value given the values of its children.
+fn value(node: &Node, child_values: &[u32]) -> u32 {
     if let 0 = node.children.len() {
         node.metadata.iter().sum::<u32>()
     } else {
         node.metadata
             .iter()
-            .map(|m| {
-                let idx = (m - 1) as usize;
-                if let Some(c) = node.children.get(idx) {
-                    root_sum(c)
-                } else {
-                    0
-                }
-            })
+            .filter_map(|&m| child_values.get((m as usize).checked_sub(1)?))
             .sum::<u32>()
     }
 }
 
-fn part2(input: &str) -> u32 {
-    let node = match parse_input(CompleteStr(input)) {
-        Ok((_incomplete, node)) => node,
-        Err(e) => panic!("{}", e),
-    };
-    root_sum(&node)
+fn root_sum(root: &Node) -> u32 {
+    // Each node waits on the stack until the values of all of its children are in
+    let mut stack = vec![(root, Vec::new())];
+    loop {
+        let (node, child_values) = stack.last().unwrap();
+        let node: &Node = node;
+        if let Some(child) = node.children.get(child_values.len()) {
+            stack.push((child, Vec::with_capacity(child.children.len())));
+            continue;
+        }
+        let value = value(node, child_values);
+        stack.pop();
+        match stack.last_mut() {
+            Some((_parent, siblings)) => siblings.push(value),
+            None => return value,
+        }
+    }
+}
+
+fn part2(input: &str) -> Result<u32> {
+    Ok(root_sum(&parse_input(input)?))
+}
+
+/// A node's child index within its parent, and the parent's position in a `TreeView`.
+type Link = (usize, usize);
+
+/// A node along with figures worked out for it.
+struct Entry<'a> {
+    node: &'a Node,
+    parent: Option<Link>,
+    depth: usize,
+    children: Vec<usize>,
+    /// Metadata sum over the whole subtree, as in part 1.
+    sum: u32,
+    /// The node's value, as in part 2.
+    value: u32,
+}
+
+/// Every node of a tree in depth-first order, for printing and looking up subtrees.
+struct TreeView<'a> {
+    entries: Vec<Entry<'a>>,
 }
 
-fn main() -> std::io::Result<()> {
+impl<'a> TreeView<'a> {
+    fn new(root: &'a Node) -> Self {
+        let mut entries: Vec<Entry> = Vec::new();
+        // (node, link to its parent, depth)
+        let mut stack: Vec<(&Node, Option<Link>, usize)> = vec![(root, None, 0)];
+        while let Some((node, parent, depth)) = stack.pop() {
+            let idx = entries.len();
+            if let Some((_child, parent_idx)) = parent {
+                entries[parent_idx].children.push(idx);
+            }
+            entries.push(Entry {
+                node,
+                parent,
+                depth,
+                children: Vec::with_capacity(node.children.len()),
+                sum: 0,
+                value: 0,
+            });
+            stack.extend(
+                node.children
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, child)| (child, Some((i, idx)), depth + 1)),
+            );
+        }
+
+        // Children always come after their parents, so working backwards fills them in first
+        for idx in (0..entries.len()).rev() {
+            let entry = &entries[idx];
+            let child_values = entry
+                .children
+                .iter()
+                .map(|&c| entries[c].value)
+                .collect::<Vec<_>>();
+            let sum = entry.node.metadata.iter().sum::<u32>()
+                + entry.children.iter().map(|&c| entries[c].sum).sum::<u32>();
+            let value = value(entry.node, &child_values);
+            entries[idx].sum = sum;
+            entries[idx].value = value;
+        }
+        TreeView { entries }
+    }
+
+    /// The path to an entry: child indices from the root, counting from zero, joined with `/`.
+    /// The root's path is empty.
+    fn path(&self, mut idx: usize) -> String {
+        let mut steps = Vec::new();
+        while let Some((child, parent)) = self.entries[idx].parent {
+            steps.push(child.to_string());
+            idx = parent;
+        }
+        steps.reverse();
+        steps.join("/")
+    }
+
+    /// Looks up a subtree by a path like `0/2/1`.
+    fn find(&self, path: &str) -> Result<&Entry<'a>> {
+        let mut idx = 0;
+        for step in path.split('/').filter(|s| !s.is_empty()) {
+            let child = step
+                .parse::<usize>()
+                .map_err(|e| format!("invalid path step {:?}: {}", step, e))?;
+            idx = *self.entries[idx]
+                .children
+                .get(child)
+                .ok_or_else(|| format!("{} has no child {}", self.describe(idx), child))?;
+        }
+        Ok(&self.entries[idx])
+    }
+
+    fn describe(&self, idx: usize) -> String {
+        match self.path(idx).as_str() {
+            "" => "the root".to_string(),
+            path => format!("node {}", path),
+        }
+    }
+
+    /// Renders the tree in Graphviz DOT format.
+    fn to_dot(&self) -> String {
+        let mut lines = vec!["digraph license {".to_string()];
+        lines.push("    node [shape=box];".to_string());
+        for (idx, entry) in self.entries.iter().enumerate() {
+            lines.push(format!(
+                "    n{} [label=\"{:?}\\nsum {}\\nvalue {}\"];",
+                idx, entry.node.metadata, entry.sum, entry.value
+            ));
+            for (i, child) in entry.children.iter().enumerate() {
+                lines.push(format!("    n{} -> n{} [label=\"{}\"];", idx, child, i));
+            }
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+impl std::fmt::Display for TreeView<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let lines = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let path = self.path(idx);
+                format!(
+                    "{}{}: metadata {:?}, sum {}, value {}",
+                    "  ".repeat(entry.depth),
+                    if path.is_empty() { "root" } else { &path },
+                    entry.node.metadata,
+                    entry.sum,
+                    entry.value
+                )
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn main() -> Result<()> {
+    // `--generate DEPTH` prints a tree that deep instead of solving anything
+    let mut args = std::env::args()
+        .skip_while(|arg| arg != "--generate")
+        .skip(1);
+    if let Some(depth) = args.next() {
+        println!("{}", serialize(&Node::chain(depth.parse()?)));
+        return Ok(());
+    }
 
     let input = std::fs::read_to_string("day8/input.txt")?;
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    println!("Part 1: {}", part1(&input)?);
+    println!("Part 2: {}", part2(&input)?);
+
+    // `--outline` or `--dot` prints the whole tree, and `--serialize` writes it back out as
+    // input; anything else is looked up as a path such as `0/2/1`
+    if let Some(arg) = std::env::args().nth(1) {
+        let root = parse_input(&input)?;
+        let view = TreeView::new(&root);
+        match arg.as_str() {
+            "--outline" => println!("{}", view),
+            "--dot" => println!("{}", view.to_dot()),
+            "--serialize" => println!("{}", serialize(&root)),
+            path => {
+                let entry = view.find(path)?;
+                println!(
+                    "children: {}, metadata: {:?}, sum: {}, value: {}",
+                    entry.node.children.len(),
+                    entry.node.metadata,
+                    entry.sum,
+                    entry.value
+                );
+            }
+        }
+    }
     Ok(())
 }
 
@@ -76,12 +346,88 @@ mod tests {
     #[test]
     fn test_part1() {
         let test_input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
-        assert_eq!(part1(test_input), 138);
+        assert_eq!(part1(test_input).unwrap(), 138);
     }
 
     #[test]
     fn test_part2() {
         let test_input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
-        assert_eq!(part2(test_input), 66);
+        assert_eq!(part2(test_input).unwrap(), 66);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let test_input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+        assert_eq!(serialize(&parse_input(test_input).unwrap()), test_input);
+
+        let tree = Node::new(
+            vec![Node::new(vec![], vec![5]), Node::new(vec![], vec![])],
+            vec![1, 1, 2],
+        );
+        let text = serialize(&tree);
+        assert_eq!(text, "2 3 0 1 5 0 0 1 1 2");
+        assert_eq!(part1(&text).unwrap(), 9);
+        assert_eq!(part2(&text).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for input in &[
+            "",
+            "1 1",
+            "0 3 1 2",
+            "1 1 0 1 5",
+            "0 1 5 0",
+            "0 1 x",
+            "0 1 -1",
+        ] {
+            assert!(parse_input(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        let depth = 200_000;
+        let tree = Node::chain(depth);
+        let text = serialize(&tree);
+        let parsed = parse_input(&text).unwrap();
+        assert_eq!(metadata_sum(&parsed), depth + 1);
+        assert_eq!(root_sum(&parsed), 2);
+        assert_eq!(serialize(&parsed), text);
+
+        assert_eq!(serialize(&Node::chain(1)), "0 1 2");
+        assert_eq!(serialize(&Node::chain(0)), "0 1 2");
+        assert_eq!(serialize(&Node::chain(3)), "1 1 1 1 0 1 2 1 1");
+    }
+
+    #[test]
+    fn test_tree_view() {
+        let root = parse_input("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        let view = TreeView::new(&root);
+        assert_eq!(
+            view.to_string(),
+            "\
+root: metadata [1, 1, 2], sum 138, value 66
+  0: metadata [10, 11, 12], sum 33, value 33
+  1: metadata [2], sum 101, value 0
+    1/0: metadata [99], sum 99, value 99"
+        );
+
+        let entry = view.find("1/0").unwrap();
+        assert_eq!((entry.sum, entry.value), (99, 99));
+        assert_eq!(entry.node.metadata, [99]);
+        assert_eq!(view.find("").unwrap().value, 66);
+        assert_eq!(view.find("/1/").unwrap().sum, 101);
+        assert_eq!(
+            view.find("1/1").err().unwrap().to_string(),
+            "node 1 has no child 1"
+        );
+        assert!(view.find("x").is_err());
+
+        let dot = view.to_dot();
+        assert!(dot.starts_with("digraph license {"));
+        assert!(dot.contains("n0 [label=\"[1, 1, 2]\\nsum 138\\nvalue 66\"];"));
+        assert!(dot.contains("n2 -> n3 [label=\"0\"];"));
+        assert_eq!(dot.matches("->").count(), 3);
     }
 }
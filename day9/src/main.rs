@@ -1,93 +1,151 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::VecDeque;
 
 #[derive(Clone, Debug, PartialEq)]
 struct GameConfig {
     num_players: usize,
     last_marble_worth: u32,
+    /// Marbles that are a multiple of this score instead of being placed; 0 means none do.
+    scoring_interval: u32,
+    /// How many marbles counter-clockwise of the current one a scoring turn removes.
+    removal_offset: usize,
 }
 
-#[derive(Default)]
-struct Marble {
-    value: u32,
-    next: Option<Rc<RefCell<Marble>>>,
-    prev: Option<Rc<RefCell<Marble>>>,
-}
-
-impl std::fmt::Debug for Marble {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}, next: {:?}, prev: {:?}",
-            &self.value,
-            self.next.as_ref().unwrap().borrow().value,
-            self.prev.as_ref().unwrap().borrow().value
-        )
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            num_players: 1,
+            last_marble_worth: 0,
+            scoring_interval: 23,
+            removal_offset: 7,
+        }
     }
 }
 
+/// The circle of marbles, kept rotated so that the current marble is always at the back.
+/// Clockwise runs from the front of the deque to the back and wraps around.
 #[derive(Debug)]
 struct Game {
-    current: Rc<RefCell<Marble>>,
+    circle: VecDeque<u32>,
+    scoring_interval: u32,
+    removal_offset: usize,
 }
 
 impl Game {
-    fn new() -> Self {
-        let marble = Rc::new(RefCell::new(Marble {
-            ..Default::default()
-        }));
-        marble.borrow_mut().prev = Some(Rc::clone(&marble));
-        marble.borrow_mut().next = Some(Rc::clone(&marble));
+    fn new(config: &GameConfig) -> Self {
+        let mut circle = VecDeque::with_capacity(config.last_marble_worth as usize + 1);
+        circle.push_back(0);
         Game {
-            current: Rc::clone(&marble),
+            circle,
+            scoring_interval: config.scoring_interval,
+            removal_offset: config.removal_offset,
         }
     }
-    fn add_marble(&mut self, value: u32) -> u32 {
-        if value % 23 == 0 {
-            for _ in 0..7 {
-                let prev = Rc::clone(self.current.borrow().prev.as_ref().unwrap());
-                self.current = prev;
-            }
-            let score = value + self.current.borrow().value;
 
-            let prev = Rc::clone(self.current.borrow().prev.as_ref().unwrap());
-            prev.borrow_mut().next = Some(Rc::clone(self.current.borrow().next.as_ref().unwrap()));
-            let next = Rc::clone(self.current.borrow().next.as_ref().unwrap());
-            next.borrow_mut().prev = Some(Rc::clone(self.current.borrow().prev.as_ref().unwrap()));
-            self.current = next;
+    fn current(&self) -> u32 {
+        *self.circle.back().expect("empty circle")
+    }
 
-            score
+    /// The marbles in clockwise order, starting from marble 0.
+    fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        let zero = self.circle.iter().position(|&m| m == 0).unwrap_or(0);
+        self.circle
+            .range(zero..)
+            .chain(self.circle.range(..zero))
+            .copied()
+    }
+
+    /// Plays a marble, returning the marble it removed if it scored.
+    fn add_marble(&mut self, value: u32) -> Option<u32> {
+        if value.is_multiple_of(self.scoring_interval) && self.circle.len() > 1 {
+            self.circle
+                .rotate_right(self.removal_offset % self.circle.len());
+            let removed = self.circle.pop_back().expect("empty circle");
+            self.circle.rotate_left(1);
+            Some(removed)
         } else {
-            for _ in 0..2 {
-                let next = Rc::clone(self.current.borrow().next.as_ref().unwrap());
-                self.current = next;
-            }
-            let new = Rc::new(RefCell::new(Marble {
-                value,
-                ..Default::default()
-            }));
-            let prev = Rc::clone(self.current.borrow().prev.as_ref().unwrap());
-            prev.borrow_mut().next = Some(Rc::clone(&new));
-            new.borrow_mut().prev = Some(Rc::clone(&prev));
-            let next = Rc::clone(&self.current);
-            next.borrow_mut().prev = Some(Rc::clone(&new));
-            new.borrow_mut().next = Some(Rc::clone(&next));
-            self.current = new;
-            0
+            self.circle.rotate_left(1);
+            self.circle.push_back(value);
+            None
         }
     }
 }
 
-fn part1(config: &GameConfig) -> u32 {
-    let mut game = Game::new();
+/// What happened on a single turn.
+#[derive(Clone, Debug, PartialEq)]
+struct Turn {
+    /// Counting from 0.
+    player: usize,
+    marble: u32,
+    /// The marble taken out of the circle, if the turn scored.
+    removed: Option<u32>,
+    score: u64,
+    /// The current marble once the turn is over.
+    current: u32,
+}
+
+/// Plays a whole game, handing each turn and the circle after it to `observe`, and returns every
+/// player's final score.
+fn play(config: &GameConfig, mut observe: impl FnMut(&Turn, &Game)) -> Vec<u64> {
+    let mut game = Game::new(config);
     let mut players = vec![0; config.num_players];
-    for (marble, player_idx) in (1..).zip((0..config.num_players).cycle()) {
-        players[player_idx] += game.add_marble(marble);
-        if marble == config.last_marble_worth {
-            break;
-        }
+    for (marble, player) in (1..=config.last_marble_worth).zip((0..config.num_players).cycle()) {
+        let removed = game.add_marble(marble);
+        let score = removed.map_or(0, |removed| u64::from(marble) + u64::from(removed));
+        players[player] += score;
+        observe(
+            &Turn {
+                player,
+                marble,
+                removed,
+                score,
+                current: game.current(),
+            },
+            &game,
+        );
     }
-    *players.iter().max().expect("no max value")
+    players
+}
+
+/// Every turn of a game, in order.
+fn replay(config: &GameConfig) -> Vec<Turn> {
+    let mut turns = Vec::with_capacity(config.last_marble_worth as usize);
+    play(config, |turn, _game| turns.push(turn.clone()));
+    turns
+}
+
+/// The circle after every turn, laid out as in the puzzle: `[player]` and then the marbles from
+/// 0, with the current marble in parentheses.
+fn trace(config: &GameConfig) -> String {
+    let width = config.last_marble_worth.to_string().len().max(2);
+    let line = |player: String, game: &Game| {
+        let mut line = format!("[{}]", player);
+        let mut after_current = false;
+        for marble in game.iter() {
+            if marble == game.current() {
+                line.push_str(&format!("{:>1$}", format!("({})", marble), width + 2));
+                after_current = true;
+            } else if after_current {
+                line.push_str(&format!("{:>1$}", marble, width));
+                after_current = false;
+            } else {
+                line.push_str(&format!(" {:>1$}", marble, width));
+            }
+        }
+        line
+    };
+
+    let mut lines = vec![line("-".to_string(), &Game::new(config))];
+    play(config, |turn, game| {
+        lines.push(line((turn.player + 1).to_string(), game))
+    });
+    lines.join("\n")
+}
+
+fn part1(config: &GameConfig) -> u64 {
+    *play(config, |_turn, _game| ())
+        .iter()
+        .max()
+        .expect("no max value")
 }
 
 fn parse_input(s: &str) -> GameConfig {
@@ -96,20 +154,38 @@ fn parse_input(s: &str) -> GameConfig {
     GameConfig {
         num_players,
         last_marble_worth,
+        ..Default::default()
     }
 }
 
-fn part2(config: &GameConfig) -> u32 {
+fn part2(config: &GameConfig) -> u64 {
     let mut config = (*config).clone();
     config.last_marble_worth *= 100;
     part1(&config)
 }
 
 fn main() -> std::io::Result<()> {
-
     let input = parse_input(std::fs::read_to_string("day9/input.txt")?.as_str());
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
+
+    // Pass `--trace N` to see every turn of the game up to marble N
+    let mut args = std::env::args().skip_while(|arg| arg != "--trace").skip(1);
+    if let Some(last_marble_worth) = args.next().and_then(|n| n.parse().ok()) {
+        let config = GameConfig {
+            last_marble_worth,
+            ..input
+        };
+        println!("{}", trace(&config));
+        for turn in replay(&config).iter().filter(|turn| turn.score > 0) {
+            println!(
+                "Player {} scored {} with marble {}",
+                turn.player + 1,
+                turn.score,
+                turn.marble
+            );
+        }
+    }
     Ok(())
 }
 
@@ -124,7 +200,9 @@ mod tests {
             parse_input(input),
             GameConfig {
                 num_players: 10,
-                last_marble_worth: 1618
+                last_marble_worth: 1618,
+                scoring_interval: 23,
+                removal_offset: 7,
             }
         )
     }
@@ -141,7 +219,7 @@ mod tests {
         ";
 
         for input in inputs.lines().filter(|&line| !line.trim().is_empty()) {
-            let score: u32 = input
+            let score: u64 = input
                 .split_whitespace()
                 .last()
                 .and_then(|s| s.parse().ok())
@@ -150,4 +228,101 @@ mod tests {
             assert_eq!(part1(&config), score)
         }
     }
+
+    #[test]
+    fn test_circle() {
+        let mut game = Game::new(&GameConfig::default());
+        for marble in 1..=22 {
+            assert_eq!(game.add_marble(marble), None);
+        }
+        assert_eq!(
+            game.iter().collect::<Vec<_>>(),
+            [0, 16, 8, 17, 4, 18, 9, 19, 2, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
+        );
+        assert_eq!(game.current(), 22);
+
+        assert_eq!(game.add_marble(23), Some(9));
+        assert_eq!(
+            game.iter().collect::<Vec<_>>(),
+            [0, 16, 8, 17, 4, 18, 19, 2, 20, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15]
+        );
+        assert_eq!(game.current(), 19);
+    }
+
+    #[test]
+    fn test_large_scores() {
+        let config = GameConfig {
+            num_players: 1,
+            last_marble_worth: 20_000,
+            ..Default::default()
+        };
+        // Well past what fits in a u32
+        assert!(part2(&config) > u64::from(u32::MAX));
+    }
+
+    #[test]
+    fn test_trace() {
+        let config = GameConfig {
+            num_players: 9,
+            last_marble_worth: 25,
+            ..Default::default()
+        };
+        let trace = trace(&config);
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 26);
+        assert_eq!(lines[0], "[-] (0)");
+        assert_eq!(lines[1], "[1]  0 (1)");
+        assert_eq!(lines[2], "[2]  0 (2) 1");
+        assert_eq!(lines[3], "[3]  0  2  1 (3)");
+        assert_eq!(
+            lines[22],
+            "[4]  0 16  8 17  4 18  9 19  2 20 10 21  5(22)11  1 12  6 13  3 14  7 15"
+        );
+        assert_eq!(
+            lines[23],
+            "[5]  0 16  8 17  4 18(19) 2 20 10 21  5 22 11  1 12  6 13  3 14  7 15"
+        );
+        assert_eq!(
+            lines[25],
+            "[7]  0 16  8 17  4 18 19  2 24 20(25)10 21  5 22 11  1 12  6 13  3 14  7 15"
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let config = GameConfig {
+            num_players: 9,
+            last_marble_worth: 25,
+            ..Default::default()
+        };
+        let turns = replay(&config);
+        assert_eq!(turns.len(), 25);
+        assert_eq!(
+            turns[22],
+            Turn {
+                player: 4,
+                marble: 23,
+                removed: Some(9),
+                score: 32,
+                current: 19
+            }
+        );
+        assert!(turns
+            .iter()
+            .filter(|t| t.marble != 23)
+            .all(|t| t.score == 0));
+
+        // A variant where every 5th marble takes the one 2 counter-clockwise
+        let config = GameConfig {
+            num_players: 2,
+            last_marble_worth: 10,
+            scoring_interval: 5,
+            removal_offset: 2,
+        };
+        let turns = replay(&config);
+        assert_eq!(turns[4].removed, Some(3));
+        assert_eq!(turns[4].current, 0);
+        assert_eq!(turns[9].removed, Some(8));
+        assert_eq!(part1(&config), 18);
+    }
 }